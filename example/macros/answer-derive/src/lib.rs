@@ -0,0 +1,25 @@
+extern crate proc_macro;
+
+use proc_macro::{TokenStream, TokenTree};
+
+#[proc_macro_derive(Answer)]
+pub fn derive_answer(input: TokenStream) -> TokenStream {
+    let mut tokens = input.into_iter();
+
+    let name = loop {
+        match tokens.next() {
+            Some(TokenTree::Ident(ref ident)) if ident.to_string() == "struct" => {
+                break tokens.next().unwrap().to_string();
+            }
+
+            Some(_) => continue,
+            None => panic!("Answer can only be derived for structs"),
+        }
+    };
+
+    format!(
+        "impl {} {{ pub fn answer() -> u32 {{ 42 }} }}",
+        name
+    ).parse()
+        .unwrap()
+}
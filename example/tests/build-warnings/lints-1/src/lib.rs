@@ -0,0 +1,17 @@
+fn unused_answer() -> u32 { //~ WARNING lint(dead_code)
+    42
+}
+
+pub fn answer() -> u32 {
+    let value = 42; //~ WARNING unused_variables
+    0
+}
+
+#[deprecated]
+fn old_answer() -> u32 {
+    42
+}
+
+pub fn deprecated_answer() -> u32 {
+    old_answer() //~ WARNING deprecated
+}
@@ -0,0 +1,8 @@
+#[deprecated(note = "use `answer` instead")]
+pub fn old_answer() -> u32 {
+    42
+}
+
+pub fn answer() -> u32 {
+    old_answer() //~ WARNING use of deprecated function `old_answer`: use `answer` instead
+}
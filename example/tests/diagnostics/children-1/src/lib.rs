@@ -0,0 +1,12 @@
+pub fn answer() -> u32 {
+    let value = 42;
+    value += 1; //~ ERROR E0384
+    //~+ HELP consider making this binding mutable
+    value
+}
+
+pub fn question() -> u32 {
+    let text: &str = String::from("42"); //~ ERROR E0308
+    //~+ HELP consider borrowing here
+    text.len() as u32
+}
@@ -0,0 +1,14 @@
+mod mod1 {
+    use mod2::func3; //~ ERROR@col 9-20 E0432
+
+    fn func1() -> u32 {
+        func3()
+    }
+}
+
+mod mod2 {
+    fn func2() -> NonExistingType {
+        0
+    }
+    //~^^^ ERROR@col 19-34 cannot find type `NonExistingType` in this scope
+}
@@ -0,0 +1,19 @@
+/// ~ Returns the answer, unlike `// ~ ERROR` in this doc comment.
+pub fn answer() -> u32 {
+    let _comment = "//~ ERROR inside of a string";
+
+    // ~/.cargo paths are not annotations either
+    "42" //~ ERROR E0308
+}
+
+pub fn quote() -> u16 {
+    let _quote = '"'; 0u32 //~ ERROR mismatched types
+}
+
+pub fn escaped<'a>(_value: &'a str) -> u16 {
+    let _escaped = "\""; 0u32 //~ ERROR mismatched types
+}
+
+pub fn raw() -> u16 {
+    let _raw = r#"a " quote"#; 0u32 //~ ERROR mismatched types
+}
@@ -0,0 +1,4 @@
+#[cfg(feature = "answer")]
+pub fn answer() -> u32 {
+    "42" //~ ERROR E0308
+}
@@ -0,0 +1,11 @@
+use std::collections::HashMap; //~ WARNING unused import: `std::collections::HashMap`
+
+pub fn answer() -> u32 {
+    let mut value = 42; //~ WARNING variable does not need to be mutable
+    value
+}
+
+pub fn answers() -> Vec<u32> {
+    let items: Vec<u32> = vec![1, 2, 3] //~ ERROR expected `;`, found `items`
+    items.into_iter().map(|item| item + 1).collect()
+}
@@ -0,0 +1,5 @@
+pub struct Point;
+pub struct Point;
+//~^ ERROR E0428
+//~| LABEL `Point` redefined here
+//~^^^^ LABEL previous definition of the type `Point` here
@@ -0,0 +1,12 @@
+use std::collections::HashMap;
+
+pub fn answer() -> u32 {
+    let mut value = 42; //~ WARNING unused variable: `value`
+    //~^ WARNING variable does not need to be mutable
+    0
+}
+
+pub fn answers() -> Vec<u32> {
+    let items: Vec<u32> = vec![1, 2, 3] //~ ERROR expected `;`, found `items`
+    items
+}
@@ -0,0 +1,9 @@
+extern "C" {
+    fn missing_external_fn();
+}
+
+fn main() {
+    unsafe {
+        missing_external_fn();
+    }
+}
@@ -0,0 +1,16 @@
+use std::collections::HashMap;
+
+macro_rules! answer {
+    ($name:ident) => {
+        pub fn $name() -> u32 {
+            "42"
+        }
+    };
+}
+
+answer!(first); //~ ERROR mismatched types
+answer!(second); //~ ERROR mismatched types
+
+pub fn answers() -> HashMap<u32, String> {
+    vec![1, 2, 3] //~ ERROR E0308
+}
@@ -0,0 +1 @@
+extern crate missing;
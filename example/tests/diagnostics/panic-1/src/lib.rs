@@ -0,0 +1,7 @@
+#[macro_use]
+extern crate answer_derive;
+
+#[derive(Answer)] //~ PANIC Answer can only be derived for structs
+pub enum Question {
+    Unknown,
+}
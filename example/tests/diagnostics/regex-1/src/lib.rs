@@ -0,0 +1,10 @@
+use std::collections::HashMap;
+
+pub fn build() -> HashMap<String, Vec<u32>> {
+    Vec::<u32>::new() //~ ERROR-REGEX ^mismatched types$
+}
+
+pub fn convert(value: Vec<Option<&str>>) -> Vec<String> {
+    value.into_iter().collect()
+    //~^ ERROR-REGEX a value of type `Vec<.+>` cannot be built from .+
+}
@@ -0,0 +1,3 @@
+pub fn distance(from: Point, to: Point) -> u32 { //~ ERROR[2] cannot find type `Point` in this scope
+    unimplemented!()
+}
@@ -0,0 +1,7 @@
+pub fn answer() -> u32 {
+    "42"
+}
+
+pub fn question() -> Question { //~ ERROR cannot find type `Question` in this scope
+    unimplemented!()
+}
@@ -0,0 +1,4 @@
+pub fn answer() -> u32 {
+    let value: u32 = "42";
+    value
+}
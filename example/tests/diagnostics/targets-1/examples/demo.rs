@@ -0,0 +1,5 @@
+extern crate targets_1;
+
+fn main() {
+    let answer: String = targets_1::answer(); //~ ERROR E0308
+}
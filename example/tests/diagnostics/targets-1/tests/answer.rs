@@ -0,0 +1,6 @@
+extern crate targets_1;
+
+#[test]
+fn it_should_answer() {
+    assert_eq!(targets_1::answer(), missing_answer()); //~ ERROR E0425
+}
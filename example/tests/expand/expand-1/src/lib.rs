@@ -1 +1,9 @@
-// TODO
+macro_rules! getter {
+    ($name:ident: $ty:ty = $value:expr) => {
+        pub fn $name() -> $ty {
+            $value
+        }
+    };
+}
+
+getter!(answer: u32 = 42);
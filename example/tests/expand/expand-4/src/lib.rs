@@ -0,0 +1,27 @@
+macro_rules! getter {
+    ($name:ident: $ty:ty = $value:expr) => {
+        pub fn $name() -> $ty {
+            $value
+        }
+    };
+}
+
+macro_rules! constant {
+    ($name:ident = $value:expr) => {
+        pub const $name: u32 = $value;
+    };
+}
+
+getter!(answer: u32 = 42); //~ EXPANDS-TO pub fn answer\(\) -> u64
+
+#[derive(Clone)] //~ EXPANDS-TO impl ::core::fmt::Debug for Point
+pub struct Point {
+    x: u32,
+}
+
+mod nested {
+    constant!(LIMIT = 10);
+    /*~^ EXPANDS-TO
+        pub const LIMIT: u32 = 11;
+    */
+}
@@ -0,0 +1,5 @@
+#[macro_use]
+extern crate answer_derive;
+
+#[derive(Answer)] //~ EXPANDS-TO impl Question \{ pub fn answer\(\) -> u32 \{ 42 \} \}
+pub struct Question;
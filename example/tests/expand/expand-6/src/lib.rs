@@ -0,0 +1,11 @@
+macro_rules! with_value {
+    ($body:expr) => {{
+        let value = "shadowed";
+        $body
+    }};
+}
+
+pub fn answer() -> u32 { //~ EXPANDS-TO let value = "shadowed"; value
+    let value = 42;
+    with_value!(value)
+}
@@ -0,0 +1,14 @@
+pub struct Wrapper(pub u8,);
+
+pub fn wrap(value: u8,) -> (Wrapper,) {
+    let (inner,) = (value,);
+    (Wrapper(inner,),)
+}
+
+pub fn unwrap(value: u8) -> u8 {
+    (wrap(value,).0).0
+}
+
+pub type Pair = (u8, u16,);
+
+pub type Single = (u8);
@@ -0,0 +1,12 @@
+macro_rules! getter {
+    ($name:ident: $ty:ty = $value:expr) => {
+        pub fn $name() -> $ty {
+            $value
+        }
+    };
+}
+
+getter!(matrix: Vec<Vec<u8>> = Vec::new());
+/*~^ EXPANDS-TO
+    pub fn matrix() -> Vec<Vec<u8>> { Vec::new() }
+*/
@@ -0,0 +1,5 @@
+extern crate expand_9;
+
+fn main() {
+    println!("{}", expand_9::answer());
+}
@@ -0,0 +1,8 @@
+mod other;
+
+pub fn answer() -> &'static str {
+    String::from("42") //~ ERROR E0308
+}
+
+//~ GLOBAL-ERROR-REGEX aborting due to
+//~+ HELP consider borrowing here
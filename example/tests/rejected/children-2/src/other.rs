@@ -0,0 +1,4 @@
+//~+ HELP consider borrowing here
+pub fn question() -> &'static str {
+    String::from("42")
+}
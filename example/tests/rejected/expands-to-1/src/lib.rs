@@ -0,0 +1,21 @@
+//~^^ EXPANDS-TO answer
+pub fn answer() -> u32 { //~ EXPANDS-TO fn answer(
+    42
+}
+
+pub fn question() -> u32 {
+    42
+}
+/*~^^^ EXPANDS-TO
+pub fn question( -> u32 {
+    42
+}
+*/
+
+pub struct Empty;
+/*~^ EXPANDS-TO
+*/
+
+pub struct Point;
+/*~^ EXPANDS-TO
+pub struct Point;
@@ -0,0 +1,8 @@
+mod other;
+
+pub struct Point;
+pub struct Point;
+//~^ ERROR E0428
+
+//~ GLOBAL-ERROR-REGEX aborting due to
+//~| LABEL `Point` redefined here
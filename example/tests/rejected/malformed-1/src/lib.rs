@@ -0,0 +1,4 @@
+//~^ ERROR E0412
+pub fn distance(from: Point, to: Point) -> u32 { //~ ERROR-REGEX (Point
+    unimplemented!() //~ LABEL[2] not found in this scope
+} //~ EROR typo
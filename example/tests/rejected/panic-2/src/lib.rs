@@ -0,0 +1,7 @@
+#[macro_use]
+extern crate answer_derive;
+
+#[derive(Answer)]
+pub enum Question {
+    Unknown,
+}
@@ -9,6 +9,23 @@ fn fail_tests(tester: &mut TestRunner) {
     });
 }
 
+fn diagnostics_tests(tester: &mut TestRunner) {
+    tester.add("diagnostics tests", || {
+        let mut config = Config::new(Mode::BuildFail, "tests/diagnostics");
+        config.strict_levels = vec![DiagnosticLevel::Error];
+        config.macro_backtrace = MacroBacktrace::CallSite;
+        config.deny_proc_macro_panics = true;
+
+        config
+    });
+}
+
+fn warnings_tests(tester: &mut TestRunner) {
+    tester.add("build-warnings tests", || {
+        Config::new(Mode::BuildWarnings, "tests/build-warnings")
+    });
+}
+
 fn success_tests(tester: &mut TestRunner) {
     tester.add("build-success tests", || {
         Config::new(Mode::BuildSuccess, "tests/build-success")
@@ -17,8 +34,17 @@ fn success_tests(tester: &mut TestRunner) {
 
 fn expansion_tests(tester: &mut TestRunner) {
     tester.add("expansion tests", || {
-        Config::new(Mode::Expand, "tests/expand")
+        let mut config = Config::new(Mode::Expand, "tests/expand");
+        config.add_cargo_env("RUSTC_BOOTSTRAP", "1");
+
+        config
     });
 }
 
-bootstrap_compilation_tests![fail_tests, success_tests, expansion_tests];
+bootstrap_compilation_tests![
+    fail_tests,
+    diagnostics_tests,
+    warnings_tests,
+    success_tests,
+    expansion_tests
+];
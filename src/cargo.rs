@@ -0,0 +1,38 @@
+use std::path::Path;
+use std::process::Command;
+
+use config::{Config, Profile};
+
+pub fn command(config: &Config, crate_path: &Path) -> Command {
+    let mut command = Command::new(&config.cargo_command);
+
+    command.current_dir(crate_path);
+
+    for (key, value) in &config.cargo_env {
+        command.env(key, value);
+    }
+
+    command
+}
+
+pub fn build_command(
+    config: &Config,
+    crate_path: &Path,
+    build_path: &Path,
+    subcommand: &str,
+) -> Command {
+    let mut command = command(config, crate_path);
+
+    command.env("CARGO_TARGET_DIR", build_path);
+    command.arg(subcommand);
+
+    if let Some(target) = config.target.as_ref() {
+        command.args(&["--target", target]);
+    }
+
+    if config.profile == Profile::Release {
+        command.arg("--release");
+    }
+
+    command
+}
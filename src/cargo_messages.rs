@@ -1,6 +1,7 @@
+use regex::Regex;
 use std::path::PathBuf;
 
-use steps::check_errors::{CompilerMessage, MessageLocation, MessageType};
+use steps::check_errors::{CompilerMessage, MessageLabel, MessageLocation, MessageType};
 
 #[derive(Debug, Clone, PartialEq, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -10,6 +11,9 @@ pub enum DiagnosticLevel {
     Note,
     Help,
 
+    #[serde(rename = "error: internal compiler error")]
+    InternalCompilerError,
+
     #[serde(rename = "")]
     Empty,
 }
@@ -23,6 +27,7 @@ pub struct Diagnostic {
 #[derive(Debug, Deserialize, Clone)]
 pub struct DiagnosticMessage {
     pub message: String,
+    pub rendered: Option<String>,
     pub level: DiagnosticLevel,
     pub code: Option<DiagnosticCode>,
     pub spans: Vec<DiagnosticSpan>,
@@ -32,8 +37,30 @@ pub struct DiagnosticMessage {
 #[derive(Debug, Deserialize, Clone)]
 pub struct DiagnosticSpan {
     pub file_name: String,
+    pub byte_start: usize,
+    pub byte_end: usize,
     pub line_start: usize,
+    pub line_end: usize,
+    pub column_start: usize,
+    pub column_end: usize,
     pub is_primary: bool,
+    pub label: Option<String>,
+    pub suggested_replacement: Option<String>,
+    pub suggestion_applicability: Option<Applicability>,
+    pub expansion: Option<Box<DiagnosticSpanMacroExpansion>>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DiagnosticSpanMacroExpansion {
+    pub span: DiagnosticSpan,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum Applicability {
+    MachineApplicable,
+    MaybeIncorrect,
+    HasPlaceholders,
+    Unspecified,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -41,12 +68,29 @@ pub struct DiagnosticCode {
     pub code: String,
 }
 
+lazy_static! {
+    pub static ref PROC_MACRO_PANIC_REGEX: Regex =
+        Regex::new(r"^(proc-macro derive|custom attribute|proc macro) panicked").unwrap();
+    static ref LINKER_ERROR_REGEX: Regex = Regex::new(r"^(linking with|linker) `").unwrap();
+}
+
+pub const PANIC_PAYLOAD_PREFIX: &str = "message: ";
+
 impl Default for DiagnosticSpan {
     fn default() -> Self {
         DiagnosticSpan {
             file_name: "unknown".into(),
+            byte_start: 0,
+            byte_end: 0,
             line_start: 1,
+            line_end: 1,
+            column_start: 1,
+            column_end: 1,
             is_primary: true,
+            label: None,
+            suggested_replacement: None,
+            suggestion_applicability: None,
+            expansion: None,
         }
     }
 }
@@ -64,22 +108,100 @@ impl<'a> From<&'a str> for DiagnosticLevel {
     }
 }
 
+impl DiagnosticMessage {
+    pub fn is_internal_compiler_error(&self) -> bool {
+        self.level == DiagnosticLevel::InternalCompilerError
+    }
+
+    pub fn is_proc_macro_panic(&self) -> bool {
+        self.level == DiagnosticLevel::Error && PROC_MACRO_PANIC_REGEX.is_match(&self.message)
+    }
+
+    pub fn is_linker_error(&self) -> bool {
+        self.level == DiagnosticLevel::Error && LINKER_ERROR_REGEX.is_match(&self.message)
+    }
+
+    pub fn is_summary(&self) -> bool {
+        self.message.starts_with("aborting")
+    }
+
+    pub fn panic_payload(&self) -> Option<&str> {
+        self.children
+            .iter()
+            .filter(|child| child.level == DiagnosticLevel::Help)
+            .find(|child| child.message.starts_with(PANIC_PAYLOAD_PREFIX))
+            .map(|child| &child.message[PANIC_PAYLOAD_PREFIX.len()..])
+    }
+
+    pub fn with_call_sites(self) -> Self {
+        DiagnosticMessage {
+            spans: self.spans.iter().map(DiagnosticSpan::with_call_site).collect(),
+            children: self.children
+                .into_iter()
+                .map(DiagnosticMessage::with_call_sites)
+                .collect(),
+
+            ..self
+        }
+    }
+}
+
+impl DiagnosticSpan {
+    pub fn call_site(&self) -> &DiagnosticSpan {
+        match self.expansion {
+            Some(ref expansion) => expansion.span.call_site(),
+            None => self,
+        }
+    }
+
+    pub fn with_call_site(&self) -> DiagnosticSpan {
+        DiagnosticSpan {
+            is_primary: self.is_primary,
+            label: self.label.clone(),
+            expansion: None,
+
+            ..self.call_site().clone()
+        }
+    }
+
+    pub fn location(&self) -> MessageLocation {
+        MessageLocation {
+            columns: match self.line_start == self.line_end {
+                true => Some((self.column_start, self.column_end)),
+                false => None,
+            },
+
+            file: PathBuf::from(&self.file_name),
+            line: self.line_start,
+        }
+    }
+}
+
 impl Into<CompilerMessage> for DiagnosticMessage {
     fn into(self) -> CompilerMessage {
         let location = self.spans
-            .into_iter()
+            .iter()
             .filter(|item| item.is_primary)
             .nth(0)
-            .map(|span| MessageLocation {
-                file: PathBuf::from(span.file_name),
-                line: span.line_start,
-            });
+            .map(DiagnosticSpan::location);
+
+        let labels = self.spans
+            .iter()
+            .filter_map(|span| {
+                span.label.as_ref().map(|text| MessageLabel {
+                    location: span.location(),
+                    text: text.clone(),
+                })
+            })
+            .collect();
 
         CompilerMessage {
             message: MessageType::Text(self.message),
             level: self.level,
             code: self.code.map(|item| item.code),
             location,
+            labels,
+            children: self.children.into_iter().map(|item| item.into()).collect(),
         }
     }
 }
@@ -0,0 +1,199 @@
+use failure::ResultExt;
+use serde_json as json;
+use std::path::{Path, PathBuf};
+use cargo;
+use config::Config;
+use error::Result;
+
+const BUILD_ARGS_METADATA_POINTER: &str = "/crate-compile-test/build-args";
+
+#[derive(Debug, Deserialize)]
+pub struct Metadata {
+    pub packages: Vec<Package>,
+    pub resolve: Option<Resolve>,
+
+    #[serde(skip)]
+    manifest_path: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Package {
+    pub id: String,
+    pub name: String,
+    pub manifest_path: PathBuf,
+    pub targets: Vec<Target>,
+    pub dependencies: Vec<Dependency>,
+    pub metadata: Option<json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Target {
+    pub name: String,
+    pub kind: Vec<String>,
+    pub src_path: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Dependency {
+    pub name: String,
+    pub rename: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Resolve {
+    pub nodes: Vec<ResolveNode>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResolveNode {
+    pub id: String,
+
+    #[serde(default)]
+    pub deps: Vec<ResolveDependency>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResolveDependency {
+    pub name: String,
+    pub pkg: String,
+}
+
+pub struct ProcMacroDependency {
+    pub manifest_name: String,
+    pub crate_name: String,
+}
+
+impl Metadata {
+    pub fn read(config: &Config, crate_path: &Path) -> Result<Self> {
+        Self::read_with_args(config, crate_path, &["--no-deps"])
+    }
+
+    pub fn read_resolved(config: &Config, crate_path: &Path) -> Result<Self> {
+        Self::read_with_args(config, crate_path, &[])
+    }
+
+    fn read_with_args(config: &Config, crate_path: &Path, args: &[&str]) -> Result<Self> {
+        let raw_output = cargo::command(config, crate_path)
+            .args(&["metadata", "--format-version", "1"])
+            .args(args)
+            .output()?;
+
+        if !raw_output.status.success() {
+            bail!(
+                "Unable to read crate metadata:\n{}",
+                String::from_utf8_lossy(&raw_output.stderr)
+            );
+        }
+
+        let mut metadata: Metadata = {
+            json::from_slice(&raw_output.stdout).context("Unable to parse Cargo metadata")?
+        };
+
+        metadata.manifest_path = crate_path.join("Cargo.toml").canonicalize()?;
+
+        Ok(metadata)
+    }
+
+    pub fn root_package(&self) -> Result<&Package> {
+        match self
+            .packages
+            .iter()
+            .find(|item| item.manifest_path == self.manifest_path)
+        {
+            Some(package) => Ok(package),
+
+            None => bail!(
+                "Unable to find the crate package: {:?} is a virtual manifest",
+                self.manifest_path
+            ),
+        }
+    }
+
+    pub fn build_args(&self) -> Vec<String> {
+        self.root_package()
+            .ok()
+            .and_then(|package| package.metadata.as_ref())
+            .and_then(|metadata| metadata.pointer(BUILD_ARGS_METADATA_POINTER))
+            .and_then(|args| args.as_array())
+            .map(|args| {
+                args.iter()
+                    .filter_map(|item| item.as_str())
+                    .map(|item| item.to_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn proc_macro_dependencies(&self) -> Vec<ProcMacroDependency> {
+        let root_package = match self.root_package() {
+            Ok(package) => package,
+            Err(_) => return vec![],
+        };
+
+        let root_node = self
+            .resolve
+            .as_ref()
+            .and_then(|item| item.nodes.iter().find(|node| node.id == root_package.id));
+
+        root_node
+            .map(|node| node.deps.iter().collect::<Vec<_>>())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|dependency| {
+                let package = self
+                    .packages
+                    .iter()
+                    .find(|package| package.id == dependency.pkg)?;
+
+                if !package.targets.iter().any(Target::is_proc_macro) {
+                    return None;
+                }
+
+                let manifest_name = root_package
+                    .dependencies
+                    .iter()
+                    .find(|item| item.name == package.name)
+                    .map(|item| item.rename.as_ref().unwrap_or(&item.name).clone())
+                    .unwrap_or_else(|| package.name.clone());
+
+                Some(ProcMacroDependency {
+                    manifest_name,
+                    crate_name: dependency.name.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Package {
+    pub fn main_target(&self) -> Option<&Target> {
+        self.targets
+            .iter()
+            .find(|target| target.kind.iter().any(|kind| kind == "lib"))
+            .or_else(|| {
+                self.targets
+                    .iter()
+                    .find(|target| target.kind.iter().any(|kind| kind == "bin"))
+            })
+    }
+}
+
+impl Target {
+    pub fn is_proc_macro(&self) -> bool {
+        self.kind.iter().any(|kind| kind == "proc-macro")
+    }
+
+    pub fn selection_args(&self) -> Vec<&str> {
+        match self.kind.iter().any(|kind| kind == "lib") {
+            true => vec!["--lib"],
+            false => vec!["--bin", &self.name],
+        }
+    }
+
+    pub fn sources_root(&self) -> &Path {
+        match self.kind.iter().any(|kind| kind == "custom-build") {
+            true => &self.src_path,
+            false => self.src_path.parent().unwrap_or(&self.src_path),
+        }
+    }
+}
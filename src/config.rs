@@ -1,11 +1,17 @@
+use std::env;
+use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 
+use matching::{ExactMatcher, MessageMatcher};
+use normalization::Normalization;
+use steps::check_errors::DiagnosticLevel;
 use steps::TestStepFactory;
 
 #[derive(PartialEq)]
 pub enum Mode {
     BuildFail,
     BuildSuccess,
+    BuildWarnings,
     Expand,
 }
 
@@ -15,6 +21,18 @@ pub enum Profile {
     Release,
 }
 
+#[derive(PartialEq)]
+pub enum MacroBacktrace {
+    Original,
+    CallSite,
+}
+
+#[derive(PartialEq)]
+pub enum ExpansionComparison {
+    Text,
+    Tokens,
+}
+
 pub struct Config {
     pub mode: Mode,
 
@@ -27,8 +45,21 @@ pub struct Config {
 
     pub crates_filter: Box<Fn(&Path) -> bool>,
     pub additional_steps: Vec<Box<TestStepFactory>>,
+
+    pub bless: bool,
+
+    pub strict_levels: Vec<DiagnosticLevel>,
+    pub message_matcher: Box<MessageMatcher>,
+    pub macro_backtrace: MacroBacktrace,
+    pub deny_proc_macro_panics: bool,
+
+    pub expansion_normalization: Vec<Normalization>,
+    pub expansion_comparison: ExpansionComparison,
+    pub expansion_build_check: bool,
 }
 
+pub const BLESS_ENV_VAR: &str = "CRATE_COMPILE_TEST_BLESS";
+
 impl Config {
     pub fn new<P: AsRef<Path>>(mode: Mode, base_dir: P) -> Self {
         Config {
@@ -43,6 +74,23 @@ impl Config {
 
             crates_filter: Box::new(|_| true),
             additional_steps: vec![],
+
+            bless: is_bless_enabled(env::var_os(BLESS_ENV_VAR)),
+
+            strict_levels: vec![
+                DiagnosticLevel::Error,
+                DiagnosticLevel::Warning,
+                DiagnosticLevel::Note,
+                DiagnosticLevel::Help,
+            ],
+
+            message_matcher: Box::new(ExactMatcher),
+            macro_backtrace: MacroBacktrace::Original,
+            deny_proc_macro_panics: false,
+
+            expansion_normalization: Normalization::default_pipeline(),
+            expansion_comparison: ExpansionComparison::Text,
+            expansion_build_check: false,
         }
     }
 
@@ -50,3 +98,10 @@ impl Config {
         self.cargo_env.push((key.into(), value.into()));
     }
 }
+
+pub fn is_bless_enabled<S: AsRef<OsStr>>(value: Option<S>) -> bool {
+    match value.as_ref().and_then(|value| value.as_ref().to_str()) {
+        Some(value) => ["1", "true"].contains(&value.trim().to_lowercase().as_str()),
+        None => false,
+    }
+}
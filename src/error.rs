@@ -5,7 +5,8 @@ use colored::*;
 use failure::Error;
 
 use formatting;
-use steps::check_errors::CompilerMessage;
+use steps::check_errors::{AnnotationError, CompilerMessage, FailureStage, ProcMacroPanic};
+use steps::expand::UnmatchedExpansion;
 
 pub type Result<T> = ::std::result::Result<T, Error>;
 
@@ -23,6 +24,48 @@ pub enum TestingError {
         missing: Vec<CompilerMessage>,
     },
 
+    MalformedAnnotations {
+        errors: Vec<AnnotationError>,
+    },
+
+    CompilerCrashed {
+        message: String,
+        query_stack: Vec<String>,
+    },
+
+    ProcMacroPanicked {
+        panics: Vec<ProcMacroPanic>,
+    },
+
+    UnexpectedFailureStage {
+        expected: FailureStage,
+        actual: FailureStage,
+    },
+
+    SnapshotMismatch {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+
+    ExpansionStructureMismatch {
+        item: String,
+        expected: Option<String>,
+        actual: Option<String>,
+    },
+
+    ExpansionAnnotationsFailed {
+        unmatched: Vec<UnmatchedExpansion>,
+    },
+
+    ExpandedCrateBuildFailed {
+        stderr: String,
+    },
+
+    FixedCrateBuildFailed {
+        stderr: String,
+    },
+
     TestFailed {
         path: PathBuf,
         error: Error,
@@ -79,6 +122,90 @@ impl fmt::Display for TestingError {
                     formatting::display_list(missing)
                 )),
             },
+
+            TestingError::MalformedAnnotations { errors } => ErrorDisplay {
+                header: "Unable to parse annotations!".into(),
+                content: Some(format!("\n{}", formatting::display_list(errors))),
+            },
+
+            TestingError::CompilerCrashed {
+                message,
+                query_stack,
+            } => ErrorDisplay {
+                header: "The compiler crashed!".into(),
+                content: Some({
+                    let mut output = format!("\nmessage: {}", message);
+
+                    if query_stack.len() > 0 {
+                        output += &format!(
+                            "\n\n{}",
+                            formatting::display_block("query stack", query_stack.join("\n"))
+                        );
+                    }
+
+                    output
+                }),
+            },
+
+            TestingError::ProcMacroPanicked { panics } => ErrorDisplay {
+                header: "Procedural macro panicked!".into(),
+                content: Some(format!("\n{}", formatting::display_list(panics))),
+            },
+
+            TestingError::UnexpectedFailureStage { expected, actual } => ErrorDisplay {
+                header: "Crate build failed at an unexpected stage!".into(),
+                content: Some(format!("\nexpected: {}\nactual:   {}", expected, actual)),
+            },
+
+            TestingError::SnapshotMismatch {
+                name,
+                expected,
+                actual,
+            } => ErrorDisplay {
+                header: format!("Snapshot `{}` doesn't match expectations!", name),
+                content: Some(format!(
+                    "\n{}",
+                    formatting::display_block("diff", formatting::display_diff(expected, actual, 3))
+                )),
+            },
+
+            TestingError::ExpansionStructureMismatch {
+                item,
+                expected,
+                actual,
+            } => ErrorDisplay {
+                header: "Expanded source structure doesn't match expectations!".into(),
+                content: Some(format!(
+                    "\nFirst mismatch at `{}`:\n\n{}\n\n{}",
+                    item,
+                    formatting::display_block(
+                        "expected",
+                        expected.as_ref().map(String::as_str).unwrap_or("<missing>")
+                    ),
+                    formatting::display_block(
+                        "actual",
+                        actual.as_ref().map(String::as_str).unwrap_or("<missing>")
+                    )
+                )),
+            },
+
+            TestingError::ExpansionAnnotationsFailed { unmatched } => ErrorDisplay {
+                header: "Expansion doesn't fulfill annotations!".into(),
+                content: Some(format!(
+                    "\nUnmatched annotations:\n{}",
+                    formatting::display_list(unmatched)
+                )),
+            },
+
+            TestingError::ExpandedCrateBuildFailed { stderr } => ErrorDisplay {
+                header: "Unable to build the expanded crate!".into(),
+                content: Some(format!("\n{}", formatting::display_block("stderr", stderr))),
+            },
+
+            TestingError::FixedCrateBuildFailed { stderr } => ErrorDisplay {
+                header: "Unable to build the crate with applied suggestions!".into(),
+                content: Some(format!("\n{}", formatting::display_block("stderr", stderr))),
+            },
         };
 
         display.fmt(f)
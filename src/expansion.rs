@@ -0,0 +1,378 @@
+use failure::ResultExt;
+use proc_macro2::{Delimiter, LineColumn, Spacing, Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn;
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+
+use error::{Result, TestingError};
+
+pub fn compare_tokens(expected: &str, actual: &str) -> Result<()> {
+    let expected = syn::parse_file(expected).context("Unable to parse expected expansion")?;
+    let actual = syn::parse_file(actual).context("Unable to parse actual expansion")?;
+
+    let expected_attrs = canonical_attrs(&expected.attrs);
+    let actual_attrs = canonical_attrs(&actual.attrs);
+
+    if expected_attrs != actual_attrs {
+        bail!(TestingError::ExpansionStructureMismatch {
+            item: "crate attributes".into(),
+            expected: Some(expected_attrs),
+            actual: Some(actual_attrs),
+        });
+    }
+
+    let items_count = expected.items.len().max(actual.items.len());
+
+    for index in 0..items_count {
+        let expected_item = expected.items.get(index);
+        let actual_item = actual.items.get(index);
+
+        let expected_tokens = expected_item.map(canonical_item_tokens);
+        let actual_tokens = actual_item.map(canonical_item_tokens);
+
+        if expected_tokens != actual_tokens {
+            bail!(TestingError::ExpansionStructureMismatch {
+                item: expected_item
+                    .or(actual_item)
+                    .map(|item| describe_item(item, index))
+                    .unwrap(),
+
+                expected: expected_tokens,
+                actual: actual_tokens,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+pub fn describe_item(item: &syn::Item, index: usize) -> String {
+    match item {
+        syn::Item::Fn(item) => format!("fn {}", item.sig.ident),
+        syn::Item::Struct(item) => format!("struct {}", item.ident),
+        syn::Item::Enum(item) => format!("enum {}", item.ident),
+        syn::Item::Union(item) => format!("union {}", item.ident),
+        syn::Item::Trait(item) => format!("trait {}", item.ident),
+        syn::Item::Type(item) => format!("type {}", item.ident),
+        syn::Item::Mod(item) => format!("mod {}", item.ident),
+        syn::Item::Const(item) => format!("const {}", item.ident),
+        syn::Item::Static(item) => format!("static {}", item.ident),
+        syn::Item::ExternCrate(item) => format!("extern crate {}", item.ident),
+        syn::Item::Use(item) => format!("use {}", canonical_tokens(item.tree.to_token_stream())),
+
+        syn::Item::Impl(item) => match item.trait_ {
+            Some((_, ref path, _)) => format!(
+                "impl {} for {}",
+                canonical_tokens(path.to_token_stream()),
+                canonical_tokens(item.self_ty.to_token_stream())
+            ),
+
+            None => format!("impl {}", canonical_tokens(item.self_ty.to_token_stream())),
+        },
+
+        syn::Item::Macro(syn::ItemMacro {
+            ident: Some(ref ident),
+            ..
+        }) => format!("macro_rules! {}", ident),
+
+        _ => format!("item #{}", index + 1),
+    }
+}
+
+pub fn find_module_items<'a>(items: &'a [syn::Item], path: &[String]) -> Option<&'a [syn::Item]> {
+    match path.split_first() {
+        None => Some(items),
+
+        Some((name, rest)) => items
+            .iter()
+            .filter_map(|item| match item {
+                syn::Item::Mod(syn::ItemMod {
+                    ident,
+                    content: Some((_, ref content)),
+                    ..
+                }) if ident == name => Some(content),
+
+                _ => None,
+            })
+            .next()
+            .and_then(|content| find_module_items(content, rest)),
+    }
+}
+
+pub fn find_item_expansion<'a>(
+    source_items: &[syn::Item],
+    expanded_items: &'a [syn::Item],
+    line: usize,
+) -> Option<&'a [syn::Item]> {
+    let index = source_items.iter().position(|item| {
+        let span = item.span();
+        span.start().line <= line && line <= span.end().line
+    })?;
+
+    let anchors = find_anchors(source_items, expanded_items);
+
+    if let (&syn::Item::Mod(ref source), Some(position)) = (&source_items[index], anchors[index]) {
+        if let (&Some((_, ref source_content)), &syn::Item::Mod(ref expanded)) =
+            (&source.content, &expanded_items[position])
+        {
+            if let Some((_, ref expanded_content)) = expanded.content {
+                return find_item_expansion(source_content, expanded_content, line);
+            }
+        }
+    }
+
+    let start = match anchors[index] {
+        Some(position) => position,
+        None => anchors[..index]
+            .iter()
+            .rev()
+            .filter_map(|position| *position)
+            .next()
+            .map(|position| position + 1)
+            .unwrap_or(0),
+    };
+
+    let end = anchors[index + 1..]
+        .iter()
+        .filter_map(|position| *position)
+        .next()
+        .unwrap_or(expanded_items.len());
+
+    Some(&expanded_items[start..end])
+}
+
+pub fn items_source_text(source: &str, items: &[syn::Item]) -> String {
+    items
+        .iter()
+        .map(|item| {
+            let (start, end) = span_offsets(source, item.span());
+            &source[start..end]
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn remove_crate_references(source: &str, crates: &[String]) -> Result<String> {
+    let file = syn::parse_file(source).context("Unable to parse expansion")?;
+
+    let mut ranges = vec![];
+    collect_crate_references(source, &file.items, crates, &mut ranges);
+
+    let mut output = source.to_owned();
+
+    for (start, end) in ranges.into_iter().rev() {
+        output.replace_range(start..end, "");
+    }
+
+    Ok(output)
+}
+
+fn collect_crate_references(
+    source: &str,
+    items: &[syn::Item],
+    crates: &[String],
+    ranges: &mut Vec<(usize, usize)>,
+) {
+    for item in items {
+        let is_reference = match item {
+            syn::Item::ExternCrate(item) => crates.iter().any(|name| item.ident == name),
+
+            syn::Item::Use(item) => match item.tree {
+                syn::UseTree::Path(ref path) => crates.iter().any(|name| path.ident == name),
+                syn::UseTree::Name(ref name) => {
+                    crates.iter().any(|crate_name| name.ident == crate_name)
+                }
+                _ => false,
+            },
+
+            syn::Item::Mod(syn::ItemMod {
+                content: Some((_, ref content)),
+                ..
+            }) => {
+                collect_crate_references(source, content, crates, ranges);
+                false
+            }
+
+            _ => false,
+        };
+
+        if is_reference {
+            ranges.push(span_offsets(source, item.span()));
+        }
+    }
+
+    ranges.sort();
+}
+
+fn span_offsets(source: &str, span: Span) -> (usize, usize) {
+    let offset = |position: LineColumn| {
+        let line_start: usize = source
+            .split('\n')
+            .take(position.line - 1)
+            .map(|line| line.len() + 1)
+            .sum();
+
+        line_start
+            + source[line_start..]
+                .char_indices()
+                .nth(position.column)
+                .map(|(offset, _)| offset)
+                .unwrap_or(source.len() - line_start)
+    };
+
+    (offset(span.start()), offset(span.end()))
+}
+
+fn find_anchors(source_items: &[syn::Item], expanded_items: &[syn::Item]) -> Vec<Option<usize>> {
+    let mut next_position = 0;
+
+    source_items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let description = describe_item(item, index);
+
+            if description.starts_with("item #") {
+                return None;
+            }
+
+            let position = expanded_items
+                .iter()
+                .enumerate()
+                .skip(next_position)
+                .position(|(position, expanded)| describe_item(expanded, position) == description)
+                .map(|offset| next_position + offset)?;
+
+            next_position = position + 1;
+            Some(position)
+        })
+        .collect()
+}
+
+fn canonical_attrs(attrs: &[syn::Attribute]) -> String {
+    attrs
+        .iter()
+        .map(|attr| canonical_tokens(attr.to_token_stream()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn canonical_snippet(snippet: &str) -> Option<String> {
+    let mut tokens = TokenStream::new();
+    let mut tuples = SingleTuples::default();
+
+    if let Ok(file) = syn::parse_file(snippet) {
+        tuples.visit_file(&file);
+        file.to_tokens(&mut tokens);
+    } else if let Ok(block) = syn::parse_str::<syn::Block>(&format!("{{ {} }}", snippet)) {
+        for stmt in &block.stmts {
+            tuples.visit_stmt(stmt);
+            stmt.to_tokens(&mut tokens);
+        }
+    } else {
+        tokens = snippet.parse().ok()?;
+    }
+
+    Some(canonical_group_tokens(tokens, &tuples.starts, false))
+}
+
+pub fn canonical_item_tokens(item: &syn::Item) -> String {
+    let mut tuples = SingleTuples::default();
+    tuples.visit_item(item);
+
+    canonical_group_tokens(item.to_token_stream(), &tuples.starts, false)
+}
+
+pub fn canonical_tokens(stream: TokenStream) -> String {
+    canonical_group_tokens(stream, &[], false)
+}
+
+// `(T,)` is a 1-tuple while `(T)` is not, so only these keep their trailing comma.
+#[derive(Default)]
+struct SingleTuples {
+    starts: Vec<LineColumn>,
+}
+
+impl<'ast> Visit<'ast> for SingleTuples {
+    fn visit_type_tuple(&mut self, node: &'ast syn::TypeTuple) {
+        if node.elems.len() == 1 {
+            self.starts.push(node.paren_token.span.start());
+        }
+
+        visit::visit_type_tuple(self, node);
+    }
+
+    fn visit_expr_tuple(&mut self, node: &'ast syn::ExprTuple) {
+        if node.elems.len() == 1 {
+            self.starts.push(node.paren_token.span.start());
+        }
+
+        visit::visit_expr_tuple(self, node);
+    }
+
+    fn visit_pat_tuple(&mut self, node: &'ast syn::PatTuple) {
+        if node.elems.len() == 1 {
+            self.starts.push(node.paren_token.span.start());
+        }
+
+        visit::visit_pat_tuple(self, node);
+    }
+}
+
+fn canonical_group_tokens(
+    stream: TokenStream,
+    single_tuples: &[LineColumn],
+    keep_trailing_comma: bool,
+) -> String {
+    let tokens: Vec<_> = stream.into_iter().collect();
+    let mut output = String::new();
+
+    let skipped_comma = match tokens.last() {
+        Some(TokenTree::Punct(punct)) if punct.as_char() == ',' && !keep_trailing_comma => {
+            Some(tokens.len() - 1)
+        }
+
+        _ => None,
+    };
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            _ if Some(index) == skipped_comma => {}
+
+            TokenTree::Punct(punct) => {
+                output.push(punct.as_char());
+
+                if punct.spacing() == Spacing::Alone {
+                    output.push(' ');
+                }
+            }
+
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", ""),
+                };
+
+                let is_single_tuple = group.delimiter() == Delimiter::Parenthesis
+                    && single_tuples.contains(&group.span().start());
+
+                output += &format!(
+                    "{}{}{} ",
+                    open,
+                    canonical_group_tokens(group.stream(), single_tuples, is_single_tuple),
+                    close
+                );
+            }
+
+            other => output += &format!("{} ", other),
+        }
+    }
+
+    output.trim_right().into()
+}
@@ -1,6 +1,15 @@
+use colored::*;
+use std::cmp;
 use std::fmt;
 use std::iter::repeat;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DiffLine {
+    Same,
+    Removed,
+    Added,
+}
+
 pub fn display_list<T: fmt::Display>(list: &Vec<T>) -> String {
     match list.len() {
         0 => "".into(),
@@ -37,6 +46,62 @@ pub fn display_block<S1: AsRef<str>, S2: AsRef<str>>(header: S1, body: S2) -> St
     )
 }
 
+pub fn display_diff<S1: AsRef<str>, S2: AsRef<str>>(
+    expected: S1,
+    actual: S2,
+    context: usize,
+) -> String {
+    let expected: Vec<_> = expected.as_ref().lines().collect();
+    let actual: Vec<_> = actual.as_ref().lines().collect();
+    let lines = diff_lines(&expected, &actual);
+
+    let mut hunks: Vec<(usize, usize)> = vec![];
+
+    for (index, _) in lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.0 != DiffLine::Same)
+    {
+        let start = index.saturating_sub(context);
+        let end = cmp::min(index + context + 1, lines.len());
+
+        match hunks.last_mut() {
+            Some(ref mut hunk) if start <= hunk.1 => hunk.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let count_lines = |range: &[(DiffLine, &str)], kind: DiffLine| {
+        range.iter().filter(|line| line.0 != kind).count()
+    };
+
+    hunks
+        .into_iter()
+        .map(|(start, end)| {
+            let header = format!(
+                "@@ -{},{} +{},{} @@",
+                count_lines(&lines[..start], DiffLine::Added) + 1,
+                count_lines(&lines[start..end], DiffLine::Added),
+                count_lines(&lines[..start], DiffLine::Removed) + 1,
+                count_lines(&lines[start..end], DiffLine::Removed),
+            );
+
+            let body = lines[start..end]
+                .iter()
+                .map(|(kind, line)| match kind {
+                    DiffLine::Same => format!(" {}", line),
+                    DiffLine::Removed => format!("-{}", line).red().to_string(),
+                    DiffLine::Added => format!("+{}", line).green().to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            format!("{}\n{}", header.cyan(), body)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 pub fn prefix_each_line<S: AsRef<str>>(input: S, prefix: &str) -> String {
     String::from(prefix) + &prefix_each_next_line(input, prefix)
 }
@@ -52,3 +117,71 @@ fn trim_lines(input: String) -> String {
         .collect::<Vec<_>>()
         .join("\n")
 }
+
+fn diff_lines<'a>(expected: &[&'a str], actual: &[&'a str]) -> Vec<(DiffLine, &'a str)> {
+    let prefix = expected
+        .iter()
+        .zip(actual)
+        .take_while(|(lhs, rhs)| lhs == rhs)
+        .count();
+
+    let suffix = expected[prefix..]
+        .iter()
+        .rev()
+        .zip(actual[prefix..].iter().rev())
+        .take_while(|(lhs, rhs)| lhs == rhs)
+        .count();
+
+    let mut lines: Vec<_> = expected[..prefix]
+        .iter()
+        .map(|line| (DiffLine::Same, *line))
+        .collect();
+
+    lines.extend(diff_changed_lines(
+        &expected[prefix..expected.len() - suffix],
+        &actual[prefix..actual.len() - suffix],
+    ));
+
+    lines.extend(
+        expected[expected.len() - suffix..]
+            .iter()
+            .map(|line| (DiffLine::Same, *line)),
+    );
+
+    lines
+}
+
+fn diff_changed_lines<'a>(expected: &[&'a str], actual: &[&'a str]) -> Vec<(DiffLine, &'a str)> {
+    let mut common = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            common[i][j] = match expected[i] == actual[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => cmp::max(common[i + 1][j], common[i][j + 1]),
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+
+    while i < expected.len() && j < actual.len() {
+        if expected[i] == actual[j] {
+            lines.push((DiffLine::Same, expected[i]));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push((DiffLine::Removed, expected[i]));
+            i += 1;
+        } else {
+            lines.push((DiffLine::Added, actual[j]));
+            j += 1;
+        }
+    }
+
+    lines.extend(expected[i..].iter().map(|line| (DiffLine::Removed, *line)));
+    lines.extend(actual[j..].iter().map(|line| (DiffLine::Added, *line)));
+
+    lines
+}
@@ -8,25 +8,40 @@ extern crate serde_derive;
 extern crate lazy_static;
 
 extern crate colored;
+extern crate proc_macro2;
+extern crate quote;
 extern crate regex;
 extern crate serde;
 extern crate serde_json;
+extern crate syn;
 extern crate tempfile;
+extern crate toml;
 extern crate walkdir;
 
 pub mod config;
 pub mod error;
+pub mod matching;
+pub mod normalization;
 pub mod steps;
 
+mod cargo;
 mod cargo_messages;
+mod cargo_metadata;
+mod expansion;
 mod formatting;
 mod plan;
 mod runner;
+mod scratch;
 
 pub mod prelude {
-    pub use config::{Config, Mode, Profile};
+    pub use config::{Config, ExpansionComparison, MacroBacktrace, Mode, Profile};
     pub use error::{Result, TestingError};
+    pub use matching::{
+        CaseInsensitiveMatcher, ExactMatcher, MessageMatcher, NormalizedMatcher, SubstringMatcher,
+    };
+    pub use normalization::Normalization;
     pub use runner::TestRunner;
+    pub use steps::check_errors::DiagnosticLevel;
 }
 
 #[macro_export]
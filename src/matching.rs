@@ -0,0 +1,52 @@
+pub trait MessageMatcher {
+    fn is_match(&self, expected: &str, actual: &str) -> bool;
+}
+
+pub struct ExactMatcher;
+
+pub struct SubstringMatcher;
+
+pub struct NormalizedMatcher;
+
+pub struct CaseInsensitiveMatcher;
+
+impl MessageMatcher for ExactMatcher {
+    fn is_match(&self, expected: &str, actual: &str) -> bool {
+        expected == actual
+    }
+}
+
+impl MessageMatcher for SubstringMatcher {
+    fn is_match(&self, expected: &str, actual: &str) -> bool {
+        actual.contains(expected)
+    }
+}
+
+impl MessageMatcher for NormalizedMatcher {
+    fn is_match(&self, expected: &str, actual: &str) -> bool {
+        let normalize = |input: &str| {
+            input
+                .replace('`', "")
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        normalize(expected) == normalize(actual)
+    }
+}
+
+impl MessageMatcher for CaseInsensitiveMatcher {
+    fn is_match(&self, expected: &str, actual: &str) -> bool {
+        expected.to_lowercase() == actual.to_lowercase()
+    }
+}
+
+impl<F> MessageMatcher for F
+where
+    F: Fn(&str, &str) -> bool,
+{
+    fn is_match(&self, expected: &str, actual: &str) -> bool {
+        self(expected, actual)
+    }
+}
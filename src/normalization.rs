@@ -0,0 +1,94 @@
+use failure::ResultExt;
+use regex::Regex;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use error::Result;
+
+pub enum Normalization {
+    StripPrelude,
+
+    StripHygiene,
+
+    NormalizeWhitespace,
+
+    Rustfmt,
+
+    Replace(Regex, String),
+
+    Custom(Box<Fn(&str) -> String>),
+}
+
+impl Normalization {
+    pub fn default_pipeline() -> Vec<Normalization> {
+        vec![Normalization::StripPrelude, Normalization::StripHygiene]
+    }
+
+    pub fn apply(&self, input: &str) -> Result<String> {
+        lazy_static! {
+            static ref PRELUDE_REGEX: Regex = Regex::new(concat!(
+                r"(?m)\A#!\[feature\(prelude_import\)\]\n(?:#!\[no_std\]\n)?",
+                r"|^(?:#\[macro_use\]\s*)?extern crate std;\n",
+                r"#\[prelude_import\]\s*use [^;]+;\n",
+                r"|^#\[prelude_import\]\s*use [^;]+;\n",
+                r"(?:(?:#\[macro_use\]\s*)?extern crate std;\n)?",
+            ))
+            .unwrap();
+            static ref HYGIENE_REGEX: Regex = Regex::new(r" ?/\* *\d+#\d+ *\*/").unwrap();
+        }
+
+        Ok(match self {
+            Normalization::StripPrelude => PRELUDE_REGEX.replace_all(input, "").into_owned(),
+
+            Normalization::StripHygiene => HYGIENE_REGEX.replace_all(input, "").into_owned(),
+
+            Normalization::NormalizeWhitespace => input
+                .lines()
+                .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+                .filter(|line| line.len() > 0)
+                .collect::<Vec<_>>()
+                .join("\n"),
+
+            Normalization::Rustfmt => rustfmt(input)?,
+
+            Normalization::Replace(regex, replacement) => {
+                regex.replace_all(input, replacement.as_str()).into_owned()
+            }
+
+            Normalization::Custom(normalize) => normalize(input),
+        })
+    }
+}
+
+pub fn normalize(input: &str, pipeline: &[Normalization]) -> Result<String> {
+    pipeline
+        .iter()
+        .fold(Ok(input.to_owned()), |output, step| step.apply(&output?))
+}
+
+fn rustfmt(input: &str) -> Result<String> {
+    let mut child = Command::new("rustfmt")
+        .args(&["--emit", "stdout"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Unable to start rustfmt")?;
+
+    child
+        .stdin
+        .take()
+        .expect("rustfmt stdin should be piped")
+        .write_all(input.as_bytes())?;
+
+    let raw_output = child.wait_with_output()?;
+
+    match raw_output.status.success() {
+        false => bail!(
+            "Unable to format the source with rustfmt:\n{}",
+            String::from_utf8_lossy(&raw_output.stderr)
+        ),
+
+        true => Ok(String::from_utf8_lossy(&raw_output.stdout).into_owned()),
+    }
+}
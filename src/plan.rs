@@ -4,7 +4,15 @@ use walkdir::WalkDir;
 
 use config::{Config, Mode};
 use error::Result;
-use steps::{build::BuildStepFactory, check_errors::CheckErrorsStepFactory, TestStepFactory};
+use steps::{
+    build::BuildStepFactory, check_errors::CheckErrorsStepFactory, expand::ExpandStepFactory,
+    fix::FixStepFactory, TestStepFactory,
+};
+
+pub enum CrateStatus {
+    Passed,
+    Blessed,
+}
 
 pub struct TestPlan {
     config: Config,
@@ -24,9 +32,12 @@ impl TestPlan {
             .collect();
 
         let mut steps: Vec<Box<TestStepFactory>> = match config.mode {
-            Mode::BuildFail => vec![Box::new(CheckErrorsStepFactory::new())],
+            Mode::BuildFail | Mode::BuildWarnings => vec![
+                Box::new(CheckErrorsStepFactory::new()),
+                Box::new(FixStepFactory::new()),
+            ],
             Mode::BuildSuccess => vec![Box::new(BuildStepFactory::new())],
-            Mode::Expand => vec![Box::new(BuildStepFactory::new())],
+            Mode::Expand => vec![Box::new(ExpandStepFactory::new())],
         };
 
         if config.additional_steps.len() > 0 {
@@ -48,18 +59,35 @@ impl TestPlan {
         (self.config.crates_filter)(crate_path) == false
     }
 
-    pub fn execute_steps(&self, crate_path: &Path) -> Result<()> {
+    pub fn execute_steps(
+        &self,
+        crate_path: &Path,
+        warnings: &mut Vec<String>,
+    ) -> Result<CrateStatus> {
         let build_path = tempdir()?;
 
         let local_steps: Vec<_> = self.steps
             .iter()
+            .filter(|factory| factory.is_applicable(crate_path))
             .map(|factory| factory.initialize(&self.config, crate_path))
             .collect();
 
+        let mut blessed = false;
+
         for step in local_steps {
-            step?.execute(&self.config, build_path.as_ref())?;
+            let step = step?;
+
+            let result = step.execute(&self.config, build_path.as_ref());
+
+            warnings.append(&mut step.warnings());
+            result?;
+
+            blessed |= step.is_blessed();
         }
 
-        Ok(())
+        match blessed {
+            true => Ok(CrateStatus::Blessed),
+            false => Ok(CrateStatus::Passed),
+        }
     }
 }
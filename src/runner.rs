@@ -6,7 +6,8 @@ use failure::Error;
 
 use config::Config;
 use error::{Result, TestingError};
-use plan::TestPlan;
+use formatting;
+use plan::{CrateStatus, TestPlan};
 
 pub struct TestRunner<'a> {
     tests: Vec<Test>,
@@ -41,6 +42,7 @@ impl<'a> TestRunner<'a> {
         let mut overall_successful: usize = 0;
         let mut overall_failed: usize = 0;
         let mut overall_ignored: usize = 0;
+        let mut overall_blessed: Vec<String> = vec![];
 
         for test in &self.tests {
             writeln!(
@@ -54,6 +56,7 @@ impl<'a> TestRunner<'a> {
             let mut successful: usize = 0;
             let mut failed: usize = 0;
             let mut ignored: usize = 0;
+            let mut blessed: Vec<String> = vec![];
 
             let errors: Vec<Error> = plan.crates()
                 .iter()
@@ -70,8 +73,25 @@ impl<'a> TestRunner<'a> {
                         return Ok(());
                     }
 
-                    match plan.execute_steps(&crate_path) {
-                        Ok(()) => {
+                    let mut warnings = vec![];
+
+                    match plan.execute_steps(&crate_path, &mut warnings) {
+                        Ok(CrateStatus::Blessed) => {
+                            writeln!(
+                                self.output.lock().unwrap(),
+                                "  testing crate {} ... {}",
+                                crate_path.to_string_lossy().bold(),
+                                "BLESSED".bright_cyan(),
+                            )?;
+
+                            self.write_warnings(&warnings)?;
+
+                            successful += 1;
+                            blessed.push(crate_path.to_string_lossy().into_owned());
+                            Ok(())
+                        }
+
+                        Ok(CrateStatus::Passed) => {
                             writeln!(
                                 self.output.lock().unwrap(),
                                 "  testing crate {} ... {}",
@@ -79,6 +99,8 @@ impl<'a> TestRunner<'a> {
                                 "OK".bright_green(),
                             )?;
 
+                            self.write_warnings(&warnings)?;
+
                             successful += 1;
                             Ok(())
                         }
@@ -91,6 +113,8 @@ impl<'a> TestRunner<'a> {
                                 "FAILED".red()
                             )?;
 
+                            self.write_warnings(&warnings)?;
+
                             failed += 1;
                             bail!(TestingError::TestFailed {
                                 path: crate_path.clone(),
@@ -111,6 +135,7 @@ impl<'a> TestRunner<'a> {
             overall_successful += successful;
             overall_failed += failed;
             overall_ignored += ignored;
+            overall_blessed.append(&mut blessed);
         }
 
         writeln!(
@@ -121,10 +146,31 @@ impl<'a> TestRunner<'a> {
             overall_ignored.to_string().yellow(),
         )?;
 
+        if !overall_blessed.is_empty() {
+            writeln!(
+                self.output.lock().unwrap(),
+                "Blessed crates:\n{}",
+                formatting::display_list(&overall_blessed)
+            )?;
+        }
+
         Ok(TestResult {
             success: overall_failed == 0,
         })
     }
+
+    fn write_warnings(&self, warnings: &[String]) -> Result<()> {
+        for warning in warnings {
+            writeln!(
+                self.output.lock().unwrap(),
+                "    {} {}",
+                "warning:".yellow(),
+                warning
+            )?;
+        }
+
+        Ok(())
+    }
 }
 
 impl TestResult {
@@ -0,0 +1,67 @@
+use failure::ResultExt;
+use std::fs::{copy, create_dir_all};
+use std::path::Path;
+use toml;
+use walkdir::WalkDir;
+
+use error::Result;
+
+pub fn copy_crate_files(from: &Path, to: &Path, root_source: Option<&Path>) -> Result<()> {
+    let sources_dir = root_source.map(|path| path.parent().unwrap_or(Path::new("")));
+
+    let entries = WalkDir::new(from)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|entry| entry.depth() > 1 || entry.file_name() != "target");
+
+    for entry in entries {
+        let entry = entry?;
+        let relative_path = entry.path().strip_prefix(from)?;
+
+        let is_source = sources_dir.map_or(false, |dir| relative_path.starts_with(dir))
+            && relative_path.extension().and_then(|item| item.to_str()) == Some("rs");
+
+        if entry.file_type().is_dir() {
+            create_dir_all(to.join(relative_path))?;
+        } else if !is_source {
+            copy(entry.path(), to.join(relative_path))?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn rewrite_manifest(crate_dir: &Path, manifest: &str, removed: &[String]) -> Result<String> {
+    let mut manifest = manifest
+        .parse::<toml::Value>()
+        .context("Unable to parse crate manifest")?;
+
+    for section in &["dependencies", "dev-dependencies", "build-dependencies"] {
+        let dependencies = match manifest
+            .get_mut(section)
+            .and_then(|item| item.as_table_mut())
+        {
+            Some(dependencies) => dependencies,
+            None => continue,
+        };
+
+        if *section == "dependencies" {
+            for name in removed {
+                dependencies.remove(name);
+            }
+        }
+
+        for (_, dependency) in dependencies.iter_mut() {
+            let absolute_path = dependency
+                .get("path")
+                .and_then(|path| path.as_str())
+                .map(|path| crate_dir.join(path).to_string_lossy().into_owned());
+
+            if let (Some(path), Some(table)) = (absolute_path, dependency.as_table_mut()) {
+                table.insert("path".into(), toml::Value::String(path));
+            }
+        }
+    }
+
+    Ok(toml::to_string(&manifest)?)
+}
@@ -1,8 +1,9 @@
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
 use super::{TestStep, TestStepFactory};
-use config::{Config, Profile};
+use cargo;
+use cargo_metadata::Metadata;
+use config::Config;
 use error::{Result, TestingError};
 
 pub struct BuildStepFactory;
@@ -31,25 +32,14 @@ impl TestStepFactory for BuildStepFactory {
 
 impl TestStep for BuildStep {
     fn execute(&self, config: &Config, build_path: &Path) -> Result<()> {
-        let mut command = Command::new(&config.cargo_command);
-
-        command.current_dir(&self.crate_dir);
-        command.env("CARGO_TARGET_DIR", build_path);
-        command.arg("build");
-
-        if let Some(target) = config.target.as_ref() {
-            command.args(&["--target", target]);
-        }
-
-        if config.profile == Profile::Release {
-            command.arg("--release");
-        }
-
-        for (key, value) in &config.cargo_env {
-            command.env(key, value);
-        }
-
-        let raw_output = command.output()?;
+        // Manifest errors are reported by the build itself.
+        let build_args = Metadata::read(config, &self.crate_dir)
+            .map(|metadata| metadata.build_args())
+            .unwrap_or_default();
+
+        let raw_output = cargo::build_command(config, &self.crate_dir, build_path, "build")
+            .args(&build_args)
+            .output()?;
         let stdout = String::from_utf8_lossy(&raw_output.stdout).into_owned();
         let stderr = String::from_utf8_lossy(&raw_output.stderr).into_owned();
 
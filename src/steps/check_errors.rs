@@ -1,25 +1,34 @@
 use failure::ResultExt;
 use regex::Regex;
 use serde_json as json;
+use std::cell::{Cell, RefCell};
 use std::cmp;
 use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use toml;
 use walkdir::WalkDir;
 
-use super::{TestStep, TestStepFactory};
-use cargo_messages;
-use config::{Config, Profile};
+use super::{read_source, TestStep, TestStepFactory};
+use cargo;
+use cargo_messages::{self, PANIC_PAYLOAD_PREFIX, PROC_MACRO_PANIC_REGEX};
+use cargo_metadata::Metadata;
+use config::{Config, MacroBacktrace, Mode};
 use error::{Result, TestingError};
+use matching::{ExactMatcher, MessageMatcher};
 
 pub use cargo_messages::DiagnosticLevel;
 
-#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub const EXPECTED_STDERR_FILE: &str = "expected.stderr";
+pub const EXPECTED_TOML_FILE: &str = "expected.toml";
+pub const EXPECTED_JSON_FILE: &str = "expected.json";
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct MessageLocation {
     pub file: PathBuf,
     pub line: usize,
+    pub columns: Option<(usize, usize)>,
 }
 
 #[derive(Debug, Clone)]
@@ -35,13 +44,83 @@ pub struct CompilerMessage {
     pub level: DiagnosticLevel,
     pub code: Option<String>,
     pub location: Option<MessageLocation>,
+    pub labels: Vec<MessageLabel>,
+    pub children: Vec<CompilerMessage>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageLabel {
+    pub location: MessageLocation,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotationError {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcMacroPanic {
+    pub location: Option<MessageLocation>,
+    pub payload: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailureStage {
+    Compile,
+    Link,
+    BuildScript,
+    ProcMacroPanic,
+    Manifest,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExpectedMessages {
+    #[serde(default)]
+    messages: Vec<ExpectedMessage>,
+
+    #[serde(rename = "failure-stage")]
+    failure_stage: Option<FailureStage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExpectedMessage {
+    file: Option<PathBuf>,
+    line: Option<usize>,
+    columns: Option<(usize, usize)>,
+    level: DiagnosticLevel,
+    code: Option<String>,
+    text: Option<String>,
+    regex: Option<String>,
+    count: Option<usize>,
+
+    #[serde(default)]
+    children: Vec<ExpectedMessage>,
 }
 
 pub struct CheckErrorsStepFactory;
 
 struct CheckErrorsStep {
     crate_dir: PathBuf,
-    expected_messages: Vec<CompilerMessage>,
+    expected_stderr: Option<String>,
+    expected_messages: Option<Vec<CompilerMessage>>,
+    expect_success: bool,
+    failure_stage: FailureStage,
+    blessed: Cell<bool>,
+    warnings: RefCell<Vec<String>>,
+}
+
+pub struct BuildOutput {
+    success: bool,
+    diagnostics: Vec<cargo_messages::DiagnosticMessage>,
+    stdout: String,
+    stderr: String,
 }
 
 impl CheckErrorsStepFactory {
@@ -49,126 +128,702 @@ impl CheckErrorsStepFactory {
         CheckErrorsStepFactory {}
     }
 
-    pub fn collect_crate_messages(crate_path: &Path) -> Result<Vec<CompilerMessage>> {
-        let sources = WalkDir::new(&crate_path.join("src"))
-            .into_iter()
-            .map(|entry| entry.unwrap())
-            .filter_map(
-                |entry| match entry.path().extension().and_then(|item| item.to_str()) {
-                    Some("rs") => Some(PathBuf::from(entry.path())),
-                    _ => None,
-                },
-            );
+    pub fn read_expected_stderr(crate_path: &Path) -> Result<String> {
+        let path = crate_path.join(EXPECTED_STDERR_FILE);
+
+        Ok(read_source(&path).context(format!("Unable to open expected stderr at {:?}", path))?)
+    }
 
+    pub fn write_expected_stderr(crate_path: &Path, contents: &str) -> Result<()> {
+        let path = crate_path.join(EXPECTED_STDERR_FILE);
+
+        File::create(&path)
+            .context(format!("Unable to write expected stderr at {:?}", path))?
+            .write_all(contents.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn read_expected_messages(crate_path: &Path) -> Result<Vec<CompilerMessage>> {
         let mut messages = vec![];
 
+        for (path, expected) in Self::read_expected_files(crate_path)? {
+            for (index, item) in expected.messages.into_iter().enumerate() {
+                let count = item.count.unwrap_or(1);
+                let message = match count {
+                    0 => Err(format_err!("`count` should be greater than zero")),
+                    _ => item.into_message(),
+                };
+
+                let message =
+                    message.context(format!("Invalid message #{} at {:?}", index + 1, path))?;
+
+                for _ in 0..count {
+                    messages.push(message.clone());
+                }
+            }
+        }
+
+        Ok(messages)
+    }
+
+    pub fn read_failure_stage(crate_path: &Path) -> Result<FailureStage> {
+        let stage = Self::read_expected_files(crate_path)?
+            .into_iter()
+            .filter_map(|(_, expected)| expected.failure_stage)
+            .nth(0);
+
+        Ok(stage.unwrap_or(FailureStage::Compile))
+    }
+
+    fn read_expected_files(crate_path: &Path) -> Result<Vec<(PathBuf, ExpectedMessages)>> {
+        let mut files = vec![];
+
+        for name in &[EXPECTED_TOML_FILE, EXPECTED_JSON_FILE] {
+            let path = crate_path.join(name);
+
+            if !path.exists() {
+                continue;
+            }
+
+            let contents = read_source(&path)
+                .context(format!("Unable to open expected messages at {:?}", path))?;
+
+            let expected: ExpectedMessages = match *name {
+                EXPECTED_TOML_FILE => toml::from_str(&contents)
+                    .context(format!("Unable to parse expected messages at {:?}", path))?,
+
+                _ => json::from_str(&contents)
+                    .context(format!("Unable to parse expected messages at {:?}", path))?,
+            };
+
+            files.push((path, expected));
+        }
+
+        Ok(files)
+    }
+
+    pub fn collect_crate_messages(
+        config: &Config,
+        crate_path: &Path,
+    ) -> Result<Vec<CompilerMessage>> {
+        let metadata = Metadata::read(config, crate_path)?;
+
+        Self::collect_package_messages(crate_path, &metadata, &mut vec![])
+    }
+
+    fn collect_package_messages(
+        crate_path: &Path,
+        metadata: &Metadata,
+        warnings: &mut Vec<AnnotationError>,
+    ) -> Result<Vec<CompilerMessage>> {
+        let crate_dir = crate_path.canonicalize()?;
+        let build_dir = crate_dir.join("target");
+
+        let package = metadata.root_package()?;
+
+        let mut sources: Vec<PathBuf> = vec![];
+
+        for target in &package.targets {
+            // Sources outside of the crate can't be reported relative to it.
+            let sources_root = match target.sources_root().canonicalize() {
+                Ok(ref root) if !root.starts_with(&crate_dir) => continue,
+                root => root?,
+            };
+
+            let entries = WalkDir::new(sources_root)
+                .into_iter()
+                .filter_entry(|entry| entry.path() != build_dir);
+
+            for entry in entries {
+                let path = PathBuf::from(entry?.path());
+
+                if path.extension().and_then(|item| item.to_str()) == Some("rs")
+                    && !sources.contains(&path)
+                {
+                    sources.push(path);
+                }
+            }
+        }
+
+        let mut annotations = vec![];
+        let mut errors = vec![];
+
         for path in sources {
-            let source_path = path.strip_prefix(crate_path)?;
-            let source_file = BufReader::new({
-                File::open(&path).context(format!("Unable to open source at {:?}", path))?
-            });
+            let source_path = path.strip_prefix(&crate_dir)?;
+            let source = read_source(&path)?;
 
-            source_file.lines().fold(1, |line_num, line| {
-                Self::analyse_source_line(&source_path, (line_num, &line.unwrap()), &mut messages);
+            let mut file_annotations = vec![];
 
-                line_num + 1
-            });
+            for (index, line) in source.lines().enumerate() {
+                if let Err(error) = Self::analyse_source_line(
+                    &source_path,
+                    (index + 1, line),
+                    &mut file_annotations,
+                    warnings,
+                ) {
+                    errors.push(error);
+                }
+            }
+
+            annotations.append(&mut file_annotations);
         }
 
+        if errors.len() > 0 {
+            bail!(TestingError::MalformedAnnotations { errors });
+        }
+
+        let mut messages = vec![];
+
+        for (message, count) in annotations {
+            for _ in 0..count {
+                messages.push(message.clone());
+            }
+        }
+
+        messages.extend(Self::read_expected_messages(crate_path)?);
+
         Ok(messages)
     }
 
-    fn analyse_source_line(path: &Path, line: (usize, &str), messages: &mut Vec<CompilerMessage>) {
+    fn analyse_source_line(
+        path: &Path,
+        line: (usize, &str),
+        messages: &mut Vec<(CompilerMessage, usize)>,
+        warnings: &mut Vec<AnnotationError>,
+    ) -> ::std::result::Result<(), AnnotationError> {
         lazy_static! {
             static ref ERR_CODE_REGEX: Regex = Regex::new(r"^ *E\d{4} *$").unwrap();
-            static ref MESSAGE_REGEX: Regex =
-                Regex::new(r"// *~([\^]+|[\|])? +(ERROR|WARNING|NOTE|HELP) +(.+)").unwrap();
+            static ref LINT_CODE_REGEX: Regex =
+                Regex::new(r"^ *lint\(([a-z0-9_]+(?:::[a-z0-9_]+)?)\) *$").unwrap();
+            static ref MESSAGE_REGEX: Regex = Regex::new(concat!(
+                r"^// *~([\^]+|[\|]|[\+])? +(ERROR|WARNING|NOTE|HELP|LABEL|PANIC)(-REGEX)?",
+                r"(?:\[(\d+)\])?(?:@col +(\d+)-(\d+))? +(.+)"
+            ))
+            .unwrap();
             static ref GLOBAL_MESSAGE_REGEX: Regex =
-                Regex::new(r"// *~ +GLOBAL-(ERROR|WARNING|NOTE|HELP)-REGEX +(.+)").unwrap();
+                Regex::new(r"^// *~ +GLOBAL-(ERROR|WARNING|NOTE|HELP)-REGEX +(.+)").unwrap();
+            static ref ANNOTATION_REGEX: Regex = Regex::new(r"^//( *)~(?:[^/]|$)").unwrap();
         }
 
-        if let Some(captures) = GLOBAL_MESSAGE_REGEX.captures(line.1) {
+        let error = |column: usize, reason: &str| AnnotationError {
+            file: path.into(),
+            line: line.0,
+            column: column + 1,
+            reason: reason.into(),
+        };
+
+        let start = match find_line_comment(line.1) {
+            Some(start) => start,
+            None => return Ok(()),
+        };
+
+        let comment = &line.1[start..];
+        let annotation = match ANNOTATION_REGEX.captures(comment) {
+            Some(captures) => captures,
+            None => return Ok(()),
+        };
+
+        if annotation[1].len() > 0 {
+            warnings.push(error(start, "Annotation has spaces between `//` and `~`"));
+        }
+
+        if let Some(captures) = GLOBAL_MESSAGE_REGEX.captures(comment) {
+            let expr = captures.get(2).unwrap();
             let message = CompilerMessage {
-                message: MessageType::Regex(Regex::new(captures[2].trim()).unwrap()),
+                message: MessageType::Regex(match Regex::new(expr.as_str().trim()) {
+                    Ok(regex) => regex,
+                    Err(_) => return Err(error(start + expr.start(), "Invalid regex")),
+                }),
 
                 code: None,
                 location: None,
                 level: captures[1].into(),
+                labels: vec![],
+                children: vec![],
             };
 
-            messages.push(message);
+            messages.push((message, 1));
+            return Ok(());
+        }
+
+        let captures = match MESSAGE_REGEX.captures(comment) {
+            Some(captures) => captures,
+
+            None => {
+                warnings.push(error(start, "Unrecognized annotation"));
+                return Ok(());
+            }
+        };
+
+        let follows_global = messages
+            .iter()
+            .last()
+            .map(|&(ref item, _)| item.location.is_none())
+            .unwrap_or(false);
+
+        let refers_previous = match captures.get(1).map(|item| item.as_str()) {
+            Some("|") | Some("+") => true,
+            _ => &captures[2] == "LABEL",
+        };
+
+        if follows_global && refers_previous {
+            return Err(error(start, "Annotation can't refer to a GLOBAL message"));
         }
 
-        if let Some(captures) = MESSAGE_REGEX.captures(line.1) {
-            let location = match captures.get(1).map(|item| item.as_str()) {
-                Some("|") => messages
+        let columns = match (captures.get(5), captures.get(6)) {
+            (Some(first), Some(last)) => match (first.as_str().parse(), last.as_str().parse()) {
+                (Ok(first), Ok(last)) => Some((first, last)),
+                _ => return Err(error(start + first.start(), "Invalid column range")),
+            },
+
+            _ => None,
+        };
+
+        let location = match captures.get(1).map(|item| item.as_str()) {
+            Some("|") => match messages.iter().last() {
+                Some(&(ref item, _)) => item.labels
                     .iter()
                     .last()
-                    .and_then(|item| item.location.clone()),
+                    .map(|label| label.location.clone())
+                    .or(item.location.clone())
+                    .map(|location| MessageLocation {
+                        columns: columns.or(location.columns),
+                        ..location
+                    }),
 
-                None => Some(MessageLocation {
-                    file: path.into(),
-                    line: line.0,
-                }),
+                None => return Err(error(start, "Annotation without a previous message")),
+            },
 
-                relative @ _ => Some(MessageLocation {
+            Some("+") => None,
+
+            None => Some(MessageLocation {
+                file: path.into(),
+                line: line.0,
+                columns,
+            }),
+
+            Some(relative) => match relative.len() < line.0 {
+                true => Some(MessageLocation {
                     file: path.into(),
-                    line: line.0 - relative.unwrap().len(),
+                    line: line.0 - relative.len(),
+                    columns,
                 }),
-            };
 
-            let (message, code) = match ERR_CODE_REGEX.is_match(&captures[3]) {
-                true => (None, Some(captures[3].trim().into())),
-                false => (Some(captures[3].trim().into()), None),
+                false => return Err(error(start, "Annotation points before the file start")),
+            },
+        };
+
+        let is_regex = captures.get(3).is_some();
+        let count = match captures.get(4).map(|item| item.as_str().parse()) {
+            Some(Ok(0)) | Some(Err(_)) => {
+                return Err(error(
+                    start + captures.get(4).unwrap().start(),
+                    "Invalid repetition count",
+                ))
+            }
+
+            Some(Ok(count)) => count,
+            None => 1,
+        };
+
+        let text = captures.get(7).unwrap();
+
+        if &captures[2] == "LABEL" {
+            if is_regex || captures.get(4).is_some() {
+                return Err(error(start, "LABEL annotation can't be a regex or repeated"));
+            }
+
+            match (messages.iter_mut().last(), location) {
+                (Some(&mut (ref mut parent, _)), Some(location)) => {
+                    parent.labels.push(MessageLabel {
+                        location,
+                        text: text.as_str().trim().into(),
+                    })
+                }
+
+                _ => return Err(error(start, "LABEL annotation without a parent message")),
+            }
+
+            return Ok(());
+        }
+
+        if &captures[2] == "PANIC" {
+            if is_regex || captures.get(1).map(|item| item.as_str()) == Some("+") {
+                return Err(error(start, "PANIC annotation can't be a regex or a child"));
+            }
+
+            let payload = CompilerMessage {
+                message: MessageType::Text(format!(
+                    "{}{}",
+                    PANIC_PAYLOAD_PREFIX,
+                    text.as_str().trim()
+                )),
+                code: None,
+                location: None,
+                level: DiagnosticLevel::Help,
+                labels: vec![],
+                children: vec![],
             };
 
             let message = CompilerMessage {
-                message: message
-                    .map(|item| MessageType::Text(item))
-                    .unwrap_or(MessageType::None),
-
-                code,
+                message: MessageType::Regex(PROC_MACRO_PANIC_REGEX.clone()),
+                code: None,
                 location,
-                level: captures[2].into(),
+                level: DiagnosticLevel::Error,
+                labels: vec![],
+                children: vec![payload],
             };
 
-            messages.push(message);
+            messages.push((message, count));
+            return Ok(());
         }
+
+        let lint = LINT_CODE_REGEX
+            .captures(text.as_str())
+            .map(|captures| captures[1].to_owned());
+
+        let (message, code) = match (is_regex, ERR_CODE_REGEX.is_match(text.as_str()), lint) {
+            (true, _, _) => match Regex::new(text.as_str().trim()) {
+                Ok(regex) => (MessageType::Regex(regex), None),
+                Err(_) => return Err(error(start + text.start(), "Invalid regex")),
+            },
+
+            (false, true, _) => (MessageType::None, Some(text.as_str().trim().into())),
+            (false, false, Some(lint)) => (MessageType::None, Some(lint)),
+            (false, false, None) => (MessageType::Text(text.as_str().trim().into()), None),
+        };
+
+        let message = CompilerMessage {
+            message,
+
+            code,
+            location,
+            level: captures[2].into(),
+            labels: vec![],
+            children: vec![],
+        };
+
+        if captures.get(1).map(|item| item.as_str()) == Some("+") {
+            if !["NOTE", "HELP"].contains(&&captures[2]) || captures.get(4).is_some() {
+                return Err(error(start, "Child annotation should be a single NOTE or HELP"));
+            }
+
+            match messages.iter_mut().last() {
+                Some(&mut (ref mut parent, _)) => parent.children.push(message),
+                None => return Err(error(start, "Child annotation without a parent message")),
+            }
+
+            return Ok(());
+        }
+
+        messages.push((message, count));
+        Ok(())
     }
 }
 
 impl CheckErrorsStep {
-    pub fn new(crate_dir: PathBuf, expected_messages: Vec<CompilerMessage>) -> Self {
+    pub fn new(
+        crate_dir: PathBuf,
+        expected_stderr: Option<String>,
+        expected_messages: Option<Vec<CompilerMessage>>,
+        expect_success: bool,
+        failure_stage: FailureStage,
+        warnings: Vec<String>,
+    ) -> Self {
         CheckErrorsStep {
             crate_dir,
+            expected_stderr,
             expected_messages,
+            expect_success,
+            failure_stage,
+            blessed: Cell::new(false),
+            warnings: RefCell::new(warnings),
+        }
+    }
+
+    fn run_build(
+        &self,
+        config: &Config,
+        path: &Path,
+        build_args: &[String],
+    ) -> Result<BuildOutput> {
+        let mut command = cargo::build_command(config, &self.crate_dir, path, "build");
+
+        command.args(&["--message-format", "json"]);
+        command.args(build_args);
+
+        let raw_output = command.output()?;
+        let mut output = BuildOutput::new(
+            raw_output.status.success(),
+            String::from_utf8_lossy(&raw_output.stdout).into_owned(),
+            String::from_utf8_lossy(&raw_output.stderr).into_owned(),
+        )?;
+
+        if config.macro_backtrace == MacroBacktrace::CallSite {
+            output.diagnostics = output
+                .diagnostics
+                .into_iter()
+                .map(|message| message.with_call_sites())
+                .collect();
+        }
+
+        Ok(output)
+    }
+
+    fn find_actual_messages(&self, output: &BuildOutput) -> Result<Vec<CompilerMessage>> {
+        let mut actual_messages = vec![];
+
+        let expected_link_failure = {
+            self.failure_stage == FailureStage::Link
+                && output.failure_stage() == FailureStage::Link
+        };
+
+        for message in &output.diagnostics {
+            if message.spans.len() == 0 {
+                for child in &message.children {
+                    actual_messages.push(child.clone().into());
+                }
+            }
+
+            if expected_link_failure && message.is_linker_error() {
+                continue;
+            }
+
+            if !message.is_summary() && message.level != DiagnosticLevel::Empty {
+                actual_messages.push(message.clone().into());
+            }
         }
+
+        self.check_build_status(output, actual_messages.len() > 0)?;
+
+        Ok(actual_messages)
     }
 
-    fn find_actual_messages(&self, config: &Config, path: &Path) -> Result<Vec<CompilerMessage>> {
-        let mut command = Command::new(&config.cargo_command);
+    fn find_actual_stderr(&self, output: &BuildOutput, build_path: &Path) -> Result<String> {
+        let rendered = output.rendered();
 
-        command.current_dir(&self.crate_dir);
-        command.env("CARGO_TARGET_DIR", path);
-        command.args(&["build", "--message-format", "json"]);
+        self.check_build_status(output, rendered.len() > 0)?;
+
+        Ok(self.normalize_stderr(&rendered, build_path))
+    }
 
-        if let Some(target) = config.target.as_ref() {
-            command.args(&["--target", target]);
+    fn is_strict(
+        &self,
+        config: &Config,
+        expected_messages: &[CompilerMessage],
+        message: &CompilerMessage,
+    ) -> bool {
+        let line = |message: &CompilerMessage| {
+            message
+                .location
+                .as_ref()
+                .map(|location| (location.file.clone(), location.line))
+        };
+
+        if config.strict_levels.contains(&message.level) {
+            return true;
         }
 
-        if config.profile == Profile::Release {
-            command.arg("--release");
+        expected_messages
+            .iter()
+            .any(|expected| expected.level == message.level && line(expected) == line(message))
+    }
+
+    fn check_build_status(&self, output: &BuildOutput, has_diagnostics: bool) -> Result<()> {
+        let stage = output.failure_stage();
+
+        // Compilation can't fail without diagnostics, so such a failure is an unknown one.
+        let is_known_stage = has_diagnostics || stage != FailureStage::Compile;
+
+        match (output.success, self.expect_success) {
+            (true, false) => bail!(TestingError::UnexpectedBuildSuccess),
+
+            (false, false) if is_known_stage && stage == self.failure_stage => Ok(()),
+
+            (false, false) if !has_diagnostics => bail!(TestingError::CrateBuildFailed {
+                stdout: output.stdout.clone(),
+                stderr: output.stderr.clone(),
+            }),
+
+            (false, true) => bail!(TestingError::CrateBuildFailed {
+                stdout: String::new(),
+                stderr: format!("{}{}", output.rendered(), output.stderr),
+            }),
+
+            _ => Ok(()),
         }
+    }
 
-        for (key, value) in &config.cargo_env {
-            command.env(key, value);
+    fn check_compiler_crash(&self, output: &BuildOutput) -> Result<()> {
+        if let Some(message) = output.ice_message() {
+            bail!(TestingError::CompilerCrashed {
+                message,
+                query_stack: output.query_stack(),
+            });
         }
 
-        let mut actual_messages = vec![];
+        Ok(())
+    }
 
-        let raw_output = command.output()?;
-        let stderr = String::from_utf8_lossy(&raw_output.stderr).into_owned();
-        let stdout = String::from_utf8_lossy(&raw_output.stdout).into_owned();
+    fn check_proc_macro_panics(
+        &self,
+        config: &Config,
+        output: &BuildOutput,
+        expected_messages: &[CompilerMessage],
+    ) -> Result<()> {
+        let panics: Vec<_> = output
+            .diagnostics
+            .iter()
+            .filter(|message| message.is_proc_macro_panic())
+            .filter(|message| {
+                let actual = (*message).clone().into();
+
+                !expected_messages
+                    .iter()
+                    .any(|expected| expected.matches(&actual, &*config.message_matcher))
+            })
+            .map(|message| ProcMacroPanic {
+                location: message
+                    .spans
+                    .iter()
+                    .find(|span| span.is_primary)
+                    .map(|span| span.location()),
+                payload: message.panic_payload().map(String::from),
+            })
+            .collect();
+
+        if panics.len() > 0 {
+            bail!(TestingError::ProcMacroPanicked { panics });
+        }
+
+        Ok(())
+    }
+
+    fn check_failure_stage(
+        &self,
+        output: &BuildOutput,
+        expected_messages: &[CompilerMessage],
+    ) -> Result<()> {
+        let actual = output.failure_stage();
+
+        if self.expect_success || actual == self.failure_stage {
+            return Ok(());
+        }
+
+        let expects_panics = expected_messages
+            .iter()
+            .any(|message| message.is_proc_macro_panic());
+
+        if actual == FailureStage::ProcMacroPanic
+            && self.failure_stage == FailureStage::Compile
+            && expects_panics
+        {
+            return Ok(());
+        }
+
+        bail!(TestingError::UnexpectedFailureStage {
+            expected: self.failure_stage,
+            actual,
+        });
+    }
+
+    fn normalize_stderr(&self, stderr: &str, build_path: &Path) -> String {
+        lazy_static! {
+            static ref RUST_SRC_REGEX: Regex =
+                Regex::new(r"[^\s]*/lib/rustlib/src/rust/|/rustc/[0-9a-f]+/").unwrap();
+            static ref RUSTC_VERSION_REGEX: Regex = Regex::new(concat!(
+                r"rustc \d+\.\d+\.\d+(?:-[\w.]+)?",
+                r"(?: \([0-9a-f]+ \d{4}-\d{2}-\d{2}\))?"
+            ))
+            .unwrap();
+        }
+
+        let mut output = stderr.replace(&*build_path.to_string_lossy(), "$TARGET");
+
+        if let Ok(crate_dir) = self.crate_dir.canonicalize() {
+            output = output.replace(&*crate_dir.to_string_lossy(), "$DIR");
+        }
+
+        let output = RUST_SRC_REGEX.replace_all(&output, "$$RUST/");
+        let output = RUSTC_VERSION_REGEX.replace_all(&output, "rustc $$VERSION");
+
+        output
+            .lines()
+            .map(|line| line.trim_right())
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_owned()
+    }
+
+    fn compare_stderr(&self, config: &Config, expected: &str, actual: &str) -> Result<()> {
+        if expected.trim() == actual {
+            return Ok(());
+        }
+
+        match config.bless {
+            true => {
+                CheckErrorsStepFactory::write_expected_stderr(
+                    &self.crate_dir,
+                    &format!("{}\n", actual),
+                )?;
+
+                self.blessed.set(true);
+                Ok(())
+            }
+
+            false => bail!(TestingError::SnapshotMismatch {
+                name: EXPECTED_STDERR_FILE.into(),
+                expected: expected.trim().into(),
+                actual: actual.into(),
+            }),
+        }
+    }
+}
+
+impl ExpectedMessage {
+    fn into_message(self) -> Result<CompilerMessage> {
+        let location = match (self.file, self.line) {
+            (Some(file), Some(line)) => Some(MessageLocation {
+                file,
+                line,
+                columns: self.columns,
+            }),
+
+            (None, None) => None,
+
+            _ => bail!("Both `file` and `line` should be specified"),
+        };
+
+        let message = match (self.text, self.regex) {
+            (Some(text), None) => MessageType::Text(text),
+            (None, Some(expr)) => MessageType::Regex(Regex::new(&expr)?),
+            (None, None) => MessageType::None,
+
+            _ => bail!("Only one of `text` and `regex` can be specified"),
+        };
+
+        let mut children = vec![];
+
+        for child in self.children {
+            children.push(child.into_message()?);
+        }
+
+        Ok(CompilerMessage {
+            message,
+            level: self.level,
+            code: self.code,
+            location,
+            labels: vec![],
+            children,
+        })
+    }
+}
+
+impl BuildOutput {
+    pub fn new(success: bool, stdout: String, stderr: String) -> Result<Self> {
+        let mut diagnostics = vec![];
 
         for line in stdout.lines() {
             let message = {
@@ -177,61 +832,209 @@ impl CheckErrorsStep {
             };
 
             match (message.reason.as_str(), message.message) {
-                ("compiler-message", Some(message)) => {
-                    if message.spans.len() == 0 {
-                        for child in &message.children {
-                            actual_messages.push(child.clone().into());
-                        }
-                    }
-
-                    if !message.message.starts_with("aborting")
-                        && message.level != DiagnosticLevel::Empty
-                    {
-                        actual_messages.push(message.into());
-                    }
-                }
-
+                ("compiler-message", Some(message)) => diagnostics.push(message),
                 _ => {}
             };
         }
 
-        match raw_output.status.success() {
-            false => {
-                if actual_messages.len() > 0 {
-                    Ok(actual_messages)
-                } else {
-                    bail!(TestingError::CrateBuildFailed { stdout, stderr })
-                }
-            }
+        Ok(BuildOutput {
+            success,
+            diagnostics,
+            stdout,
+            stderr,
+        })
+    }
+
+    pub fn failure_stage(&self) -> FailureStage {
+        lazy_static! {
+            static ref MANIFEST_ERROR_REGEX: Regex = Regex::new(concat!(
+                r"(?m)^error: (?:failed to (?:parse|load) manifest|failed to select a version",
+                r"|no matching package|failed to get `.+` as a dependency",
+                r"|cyclic package dependency|.+\n +--> \S*Cargo\.toml:\d+)"
+            ))
+            .unwrap();
+        }
+
+        if MANIFEST_ERROR_REGEX.is_match(&self.stderr) {
+            return FailureStage::Manifest;
+        }
+
+        if self.stderr.contains("failed to run custom build command") {
+            return FailureStage::BuildScript;
+        }
+
+        let causes: Vec<_> = self.diagnostics
+            .iter()
+            .filter(|message| message.level == DiagnosticLevel::Error && !message.is_summary())
+            .collect();
+
+        if causes.len() > 0 && causes.iter().all(|item| item.is_linker_error()) {
+            return FailureStage::Link;
+        }
+
+        if causes.len() > 0 && causes.iter().all(|item| item.is_proc_macro_panic()) {
+            return FailureStage::ProcMacroPanic;
+        }
+
+        FailureStage::Compile
+    }
+
+    pub fn ice_message(&self) -> Option<String> {
+        lazy_static! {
+            static ref ICE_REGEX: Regex =
+                Regex::new(r"(?m)^error: internal compiler error: (.+)$").unwrap();
+            static ref RUSTC_CRASH_REGEX: Regex = Regex::new(concat!(
+                r"process didn't exit successfully: `\S*rustc(?:\.exe)? [^`]*` ",
+                r"\(exit (?:status|code): 101\)"
+            ))
+            .unwrap();
+            static ref RUSTC_PANIC_REGEX: Regex = Regex::new(
+                r"thread 'rustc'(?: \(\d+\))? panicked at (?:'(.+)', \S+|\S+:\n(.+))"
+            ).unwrap();
+        }
+
+        if let Some(message) = self.diagnostics
+            .iter()
+            .find(|message| message.is_internal_compiler_error())
+        {
+            return Some(message.message.clone());
+        }
 
-            true => bail!(TestingError::UnexpectedBuildSuccess),
+        if let Some(captures) = ICE_REGEX.captures(&self.stderr) {
+            return Some(captures[1].into());
         }
+
+        if !RUSTC_CRASH_REGEX.is_match(&self.stderr) {
+            return None;
+        }
+
+        Some(
+            RUSTC_PANIC_REGEX
+                .captures(&self.stderr)
+                .and_then(|captures| captures.get(1).or(captures.get(2)))
+                .map(|payload| payload.as_str().to_owned())
+                .unwrap_or_else(|| "rustc exited with status 101".into()),
+        )
+    }
+
+    pub fn query_stack(&self) -> Vec<String> {
+        self.stderr
+            .lines()
+            .skip_while(|line| !line.starts_with("query stack during panic:"))
+            .skip(1)
+            .take_while(|line| !line.starts_with("end of query stack"))
+            .map(String::from)
+            .collect()
+    }
+
+    fn rendered(&self) -> String {
+        self.diagnostics
+            .iter()
+            .filter_map(|message| message.rendered.as_ref())
+            .map(|rendered| rendered.as_str())
+            .collect::<Vec<_>>()
+            .join("")
     }
 }
 
 impl TestStepFactory for CheckErrorsStepFactory {
-    fn initialize(&self, _config: &Config, crate_path: &Path) -> Result<Box<TestStep>> {
+    fn initialize(&self, config: &Config, crate_path: &Path) -> Result<Box<TestStep>> {
+        let expected_stderr = match crate_path.join(EXPECTED_STDERR_FILE).exists() {
+            true => Some(Self::read_expected_stderr(crate_path)?),
+            false => None,
+        };
+
+        let mut warnings = vec![];
+
+        let expected_messages = match (&expected_stderr, Metadata::read(config, crate_path)) {
+            (&None, Ok(metadata)) => Some(Self::collect_package_messages(
+                crate_path,
+                &metadata,
+                &mut warnings,
+            )?),
+
+            _ => None,
+        };
+
         Ok(Box::new(CheckErrorsStep::new(
             crate_path.into(),
-            Self::collect_crate_messages(crate_path)?,
+            expected_stderr,
+            expected_messages,
+            config.mode == Mode::BuildWarnings,
+            Self::read_failure_stage(crate_path)?,
+            warnings.iter().map(|item| item.to_string()).collect(),
         )))
     }
 }
 
 impl TestStep for CheckErrorsStep {
     fn execute(&self, config: &Config, build_path: &Path) -> Result<()> {
-        let actual_messages = self.find_actual_messages(config, build_path)?;
+        let metadata = Metadata::read(config, &self.crate_dir);
+        let build_args = match metadata {
+            Ok(ref metadata) => metadata.build_args(),
+            Err(_) => vec![],
+        };
+
+        let output = self.run_build(config, build_path, &build_args)?;
+
+        // Broken manifests have neither metadata nor compiler messages to check.
+        if !self.expect_success && output.failure_stage() == FailureStage::Manifest {
+            return self.check_failure_stage(&output, &[]);
+        }
+
+        let metadata = metadata?;
+
+        self.check_compiler_crash(&output)?;
+
+        if let Some(ref expected_stderr) = self.expected_stderr {
+            let actual_stderr = self.find_actual_stderr(&output, build_path)?;
+
+            self.compare_stderr(config, expected_stderr, &actual_stderr)?;
+            return self.check_failure_stage(&output, &[]);
+        }
+
+        let expected_messages = match self.expected_messages {
+            Some(ref messages) => messages.clone(),
+            None => {
+                let mut warnings = vec![];
+                let messages = CheckErrorsStepFactory::collect_package_messages(
+                    &self.crate_dir,
+                    &metadata,
+                    &mut warnings,
+                );
+
+                *self.warnings.borrow_mut() =
+                    warnings.iter().map(|item| item.to_string()).collect();
+
+                messages?
+            }
+        };
+        let actual_messages = self.find_actual_messages(&output)?;
+
+        let matches = match_messages(
+            &expected_messages,
+            &actual_messages,
+            &*config.message_matcher,
+        );
+
+        if config.deny_proc_macro_panics {
+            self.check_proc_macro_panics(config, &output, &expected_messages)?;
+        }
 
         let unexpected_messages: Vec<_> = actual_messages
-            .clone()
-            .into_iter()
-            .filter(|item| !self.expected_messages.contains(item))
+            .iter()
+            .enumerate()
+            .filter(|&(index, _)| !matches.contains(&Some(index)))
+            .map(|(_, item)| item)
+            .filter(|item| self.is_strict(config, &expected_messages, item))
+            .cloned()
             .collect();
 
-        let missing_messages: Vec<_> = self.expected_messages
-            .clone()
-            .into_iter()
-            .filter(|item| !actual_messages.contains(item))
+        let missing_messages: Vec<_> = expected_messages
+            .iter()
+            .zip(&matches)
+            .filter(|&(_, matched)| matched.is_none())
+            .map(|(item, _)| item.clone())
             .collect();
 
         if unexpected_messages.len() > 0 || missing_messages.len() > 0 {
@@ -241,22 +1044,142 @@ impl TestStep for CheckErrorsStep {
             });
         }
 
-        Ok(())
+        self.check_failure_stage(&output, &expected_messages)
+    }
+
+    fn is_blessed(&self) -> bool {
+        self.blessed.get()
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        self.warnings.borrow().clone()
     }
 }
 
-impl cmp::PartialEq for CompilerMessage {
-    fn eq(&self, other: &CompilerMessage) -> bool {
-        if self.location != other.location || self.level != other.level {
+fn match_messages(
+    expected: &[CompilerMessage],
+    actual: &[CompilerMessage],
+    matcher: &MessageMatcher,
+) -> Vec<Option<usize>> {
+    let mut matches = vec![None; expected.len()];
+
+    for index in 0..actual.len() {
+        let mut visited = vec![false; expected.len()];
+
+        find_message_match(expected, actual, matcher, index, &mut visited, &mut matches);
+    }
+
+    matches
+}
+
+fn find_message_match(
+    expected: &[CompilerMessage],
+    actual: &[CompilerMessage],
+    matcher: &MessageMatcher,
+    index: usize,
+    visited: &mut [bool],
+    matches: &mut [Option<usize>],
+) -> bool {
+    for candidate in 0..expected.len() {
+        if visited[candidate] || !expected[candidate].matches(&actual[index], matcher) {
+            continue;
+        }
+
+        visited[candidate] = true;
+
+        let available = match matches[candidate] {
+            Some(other) => find_message_match(expected, actual, matcher, other, visited, matches),
+            None => true,
+        };
+
+        if available {
+            matches[candidate] = Some(index);
+            return true;
+        }
+    }
+
+    false
+}
+
+impl cmp::PartialEq for MessageLocation {
+    fn eq(&self, other: &MessageLocation) -> bool {
+        if self.file != other.file || self.line != other.line {
             return false;
         }
 
-        if self.code.is_some() && other.code.is_some() {
-            return self.code.as_ref().unwrap() == other.code.as_ref().unwrap();
+        match (self.columns, other.columns) {
+            (Some(lhs), Some(rhs)) => lhs == rhs,
+            _ => true,
         }
+    }
+}
+
+impl CompilerMessage {
+    pub fn matches(&self, actual: &CompilerMessage, matcher: &MessageMatcher) -> bool {
+        self.location == actual.location && self.matches_content(actual, matcher)
+    }
 
-        match (&self.message, &other.message) {
-            (MessageType::Text(ref lhs), MessageType::Text(ref rhs)) => lhs == rhs,
+    fn is_proc_macro_panic(&self) -> bool {
+        match self.message {
+            MessageType::Regex(ref expr) => expr.as_str() == PROC_MACRO_PANIC_REGEX.as_str(),
+            _ => false,
+        }
+    }
+
+    fn summary(&self) -> String {
+        let prefix = match self.code {
+            Some(ref code) => format!("({:?} {}) ", self.level, code),
+            None => format!("({:?}) ", self.level),
+        };
+
+        match self.message {
+            MessageType::Text(ref message) => prefix + message,
+            MessageType::Regex(ref expr) => prefix + &format!("Regex({})", expr.as_str()),
+
+            _ => prefix,
+        }
+    }
+
+    fn matches_content(&self, actual: &CompilerMessage, matcher: &MessageMatcher) -> bool {
+        if self.level != actual.level {
+            return false;
+        }
+
+        let contains_labels = self.labels.iter().all(|expected| {
+            actual.labels.iter().any(|label| {
+                expected.location == label.location && matcher.is_match(&expected.text, &label.text)
+            })
+        });
+
+        if !contains_labels {
+            return false;
+        }
+
+        let contains_children = self.children.iter().all(|expected| {
+            actual.children.iter().any(|child| {
+                (expected.location.is_none() || expected.location == child.location)
+                    && expected.matches_content(child, matcher)
+            })
+        });
+
+        if !contains_children {
+            return false;
+        }
+
+        if self.code.is_some() && actual.code.is_some() {
+            return self.code.as_ref().unwrap() == actual.code.as_ref().unwrap();
+        }
+
+        if let (&None, &MessageType::Text(ref lhs), &Some(ref rhs)) =
+            (&self.code, &self.message, &actual.code)
+        {
+            if lhs == rhs {
+                return true;
+            }
+        }
+
+        match (&self.message, &actual.message) {
+            (MessageType::Text(ref lhs), MessageType::Text(ref rhs)) => matcher.is_match(lhs, rhs),
 
             (MessageType::Text(ref lhs), MessageType::Regex(ref rhs)) => rhs.is_match(lhs),
             (MessageType::Regex(ref lhs), MessageType::Text(ref rhs)) => lhs.is_match(rhs),
@@ -270,16 +1193,17 @@ impl cmp::PartialEq for CompilerMessage {
     }
 }
 
+impl cmp::PartialEq for CompilerMessage {
+    fn eq(&self, other: &CompilerMessage) -> bool {
+        self.matches(other, &ExactMatcher) || other.matches(self, &ExactMatcher)
+    }
+}
+
 impl fmt::Display for CompilerMessage {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.location {
             Some(ref location) => {
-                writeln!(
-                    f,
-                    "file:    {}:{}",
-                    &location.file.to_string_lossy(),
-                    location.line
-                )?;
+                writeln!(f, "file:    {}", location)?;
             }
 
             None => {
@@ -287,19 +1211,135 @@ impl fmt::Display for CompilerMessage {
             }
         };
 
-        f.write_str("message: ")?;
-        f.write_str(&match self.code {
-            Some(ref code) => format!("({:?} {}) ", self.level, code),
-            None => format!("({:?}) ", self.level),
-        })?;
+        write!(f, "message: {}", self.summary())?;
 
-        match self.message {
-            MessageType::Text(ref message) => write!(f, "{}", message)?,
-            MessageType::Regex(ref expr) => write!(f, "Regex({})", expr.as_str())?,
+        for label in &self.labels {
+            write!(f, "\nlabel:   {} {}", label.location, label.text)?;
+        }
 
-            _ => {}
+        for child in &self.children {
+            write!(f, "\nchild:   {}", child.summary())?;
         }
 
         Ok(())
     }
 }
+
+impl fmt::Display for FailureStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            FailureStage::Compile => "compile",
+            FailureStage::Link => "link",
+            FailureStage::BuildScript => "build-script",
+            FailureStage::ProcMacroPanic => "proc-macro-panic",
+            FailureStage::Manifest => "manifest",
+        })
+    }
+}
+
+impl fmt::Display for AnnotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{} {}",
+            self.file.to_string_lossy(),
+            self.line,
+            self.column,
+            self.reason
+        )
+    }
+}
+
+impl fmt::Display for ProcMacroPanic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.location {
+            Some(ref location) => write!(f, "{} ", location)?,
+            None => write!(f, "unknown location ")?,
+        };
+
+        match self.payload {
+            Some(ref payload) => write!(f, "{}", payload),
+            None => write!(f, "<no payload>"),
+        }
+    }
+}
+
+impl fmt::Display for MessageLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", &self.file.to_string_lossy(), self.line)?;
+
+        if let Some((start, end)) = self.columns {
+            write!(f, ":{}-{}", start, end)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn find_line_comment(line: &str) -> Option<usize> {
+    let chars: Vec<_> = line.char_indices().collect();
+    let char_at = |position: usize| chars.get(position).map(|item| item.1);
+    let mut position = 0;
+
+    while position < chars.len() {
+        match chars[position].1 {
+            '/' if char_at(position + 1) == Some('/') => return Some(chars[position].0),
+
+            'r' if position == 0 || !is_ident_char(chars[position - 1].1) => {
+                let hashes = chars[position + 1..]
+                    .iter()
+                    .take_while(|item| item.1 == '#')
+                    .count();
+
+                if char_at(position + hashes + 1) == Some('"') {
+                    position += hashes + 2;
+
+                    while position < chars.len()
+                        && !(chars[position].1 == '"'
+                            && (1..=hashes).all(|offset| char_at(position + offset) == Some('#')))
+                    {
+                        position += 1;
+                    }
+
+                    position += hashes;
+                }
+            }
+
+            '"' => {
+                position += 1;
+
+                while position < chars.len() && chars[position].1 != '"' {
+                    if chars[position].1 == '\\' {
+                        position += 1;
+                    }
+
+                    position += 1;
+                }
+            }
+
+            // Char literals, as opposed to lifetimes, are closed after one (maybe escaped) char.
+            '\'' => match (char_at(position + 1), char_at(position + 2)) {
+                (Some('\\'), _) => {
+                    position += 2;
+
+                    while position < chars.len() && chars[position].1 != '\'' {
+                        position += 1;
+                    }
+                }
+
+                (Some(_), Some('\'')) => position += 2,
+                _ => {}
+            },
+
+            _ => {}
+        }
+
+        position += 1;
+    }
+
+    None
+}
+
+fn is_ident_char(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
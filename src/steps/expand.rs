@@ -0,0 +1,495 @@
+use failure::ResultExt;
+use proc_macro2::TokenStream;
+use regex::{Captures, Regex};
+use std::cell::Cell;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use syn;
+use tempfile::tempdir;
+use walkdir::WalkDir;
+
+use super::check_errors::{AnnotationError, MessageLocation, MessageType};
+use super::{read_source, TestStep, TestStepFactory};
+use cargo;
+use cargo_metadata::Metadata;
+use config::{Config, ExpansionComparison};
+use error::{Result, TestingError};
+use expansion::{
+    canonical_item_tokens, canonical_snippet, compare_tokens, find_item_expansion,
+    find_module_items, items_source_text, remove_crate_references,
+};
+use formatting;
+use normalization::normalize;
+use scratch::{copy_crate_files, rewrite_manifest};
+
+pub const EXPECTED_EXPANSION_FILE: &str = "expanded.rs";
+
+#[derive(Debug, Clone)]
+pub struct ExpansionAnnotation {
+    pub location: MessageLocation,
+    pub pattern: MessageType,
+}
+
+#[derive(Debug, Clone)]
+pub struct UnmatchedExpansion {
+    pub annotation: ExpansionAnnotation,
+    pub expansion: Option<String>,
+}
+
+pub struct ExpandStepFactory;
+
+struct ExpandStep {
+    crate_dir: PathBuf,
+    expected_expansion: Option<String>,
+    annotations: Vec<ExpansionAnnotation>,
+    blessed: Cell<bool>,
+}
+
+impl ExpandStepFactory {
+    pub fn new() -> Self {
+        ExpandStepFactory {}
+    }
+
+    pub fn read_expected_expansion(crate_path: &Path) -> Result<String> {
+        let path = crate_path.join(EXPECTED_EXPANSION_FILE);
+
+        Ok(read_source(&path).context(format!("Unable to open expected expansion at {:?}", path))?)
+    }
+
+    pub fn write_expected_expansion(crate_path: &Path, contents: &str) -> Result<()> {
+        let path = crate_path.join(EXPECTED_EXPANSION_FILE);
+
+        File::create(&path)
+            .context(format!("Unable to write expected expansion at {:?}", path))?
+            .write_all(contents.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn collect_crate_annotations(crate_path: &Path) -> Result<Vec<ExpansionAnnotation>> {
+        let mut annotations = vec![];
+        let mut errors = vec![];
+
+        for entry in WalkDir::new(&crate_path.join("src")) {
+            let path = PathBuf::from(entry?.path());
+
+            if path.extension().and_then(|item| item.to_str()) != Some("rs") {
+                continue;
+            }
+
+            let source = read_source(&path)?;
+
+            Self::analyse_source(
+                path.strip_prefix(crate_path)?,
+                &source,
+                &mut annotations,
+                &mut errors,
+            );
+        }
+
+        if errors.len() > 0 {
+            bail!(TestingError::MalformedAnnotations { errors });
+        }
+
+        Ok(annotations)
+    }
+
+    fn analyse_source(
+        path: &Path,
+        source: &str,
+        annotations: &mut Vec<ExpansionAnnotation>,
+        errors: &mut Vec<AnnotationError>,
+    ) {
+        lazy_static! {
+            static ref INLINE_REGEX: Regex =
+                Regex::new(r"// *~([\^]+)? +EXPANDS-TO +(.+)").unwrap();
+            static ref BLOCK_REGEX: Regex = Regex::new(r"/\* *~([\^]+)? +EXPANDS-TO *$").unwrap();
+        }
+
+        let error = |line: usize, column: usize, reason: &str| AnnotationError {
+            file: path.into(),
+            line,
+            column: column + 1,
+            reason: reason.into(),
+        };
+
+        let mut block: Option<(usize, usize, Option<MessageLocation>, Vec<&str>)> = None;
+
+        for (index, line) in source.lines().enumerate() {
+            if let Some((start_line, start, location, mut contents)) = block.take() {
+                match line.find("*/") {
+                    Some(end) => {
+                        contents.push(&line[..end]);
+
+                        let snippet = contents.join("\n").trim().to_owned();
+
+                        match (location, snippet.parse::<TokenStream>()) {
+                            (None, _) => {}
+
+                            (Some(_), Ok(ref tokens)) if tokens.is_empty() => {
+                                errors.push(error(start_line, start, "Empty EXPANDS-TO block"))
+                            }
+
+                            (Some(location), Ok(_)) => annotations.push(ExpansionAnnotation {
+                                location,
+                                pattern: MessageType::Text(snippet),
+                            }),
+
+                            (_, Err(_)) => {
+                                errors.push(error(start_line, start, "Invalid EXPANDS-TO block"))
+                            }
+                        }
+                    }
+
+                    None => {
+                        contents.push(line);
+                        block = Some((start_line, start, location, contents));
+                    }
+                }
+
+                continue;
+            }
+
+            let location = |captures: &Captures| {
+                let offset = captures.get(1).map(|item| item.as_str().len()).unwrap_or(0);
+
+                match offset < index + 1 {
+                    true => Ok(MessageLocation {
+                        file: path.into(),
+                        line: index + 1 - offset,
+                        columns: None,
+                    }),
+
+                    false => Err(error(
+                        index + 1,
+                        captures.get(0).unwrap().start(),
+                        "Annotation points before the file start",
+                    )),
+                }
+            };
+
+            if let Some(captures) = INLINE_REGEX.captures(line) {
+                let expr = captures.get(2).unwrap();
+
+                match (location(&captures), Regex::new(expr.as_str().trim())) {
+                    (Ok(location), Ok(pattern)) => annotations.push(ExpansionAnnotation {
+                        location,
+                        pattern: MessageType::Regex(pattern),
+                    }),
+
+                    (Err(location_error), _) => errors.push(location_error),
+
+                    (_, Err(_)) => errors.push(error(
+                        index + 1,
+                        expr.start(),
+                        "Invalid EXPANDS-TO pattern",
+                    )),
+                }
+            }
+
+            if let Some(captures) = BLOCK_REGEX.captures(line) {
+                let location = match location(&captures) {
+                    Ok(location) => Some(location),
+
+                    Err(location_error) => {
+                        errors.push(location_error);
+                        None
+                    }
+                };
+
+                block = Some((index + 1, captures.get(0).unwrap().start(), location, vec![]));
+            }
+        }
+
+        if let Some((start_line, start, _, _)) = block {
+            errors.push(error(start_line, start, "Unterminated EXPANDS-TO block"));
+        }
+    }
+}
+
+impl ExpandStep {
+    pub fn new(
+        crate_dir: PathBuf,
+        expected_expansion: Option<String>,
+        annotations: Vec<ExpansionAnnotation>,
+    ) -> Self {
+        ExpandStep {
+            crate_dir,
+            expected_expansion,
+            annotations,
+            blessed: Cell::new(false),
+        }
+    }
+
+    fn find_actual_expansion(&self, config: &Config, path: &Path) -> Result<String> {
+        let metadata = Metadata::read(config, &self.crate_dir)?;
+        let mut command = cargo::build_command(config, &self.crate_dir, path, "rustc");
+
+        if let Some(target) = metadata.root_package()?.main_target() {
+            command.args(target.selection_args());
+        }
+
+        command.args(&["--", "-Zunpretty=expanded"]);
+
+        let raw_output = command.output()?;
+        let stdout = String::from_utf8_lossy(&raw_output.stdout).into_owned();
+        let stderr = String::from_utf8_lossy(&raw_output.stderr).into_owned();
+
+        match raw_output.status.success() {
+            false => bail!(TestingError::CrateBuildFailed { stdout, stderr }),
+            true => Ok(stdout),
+        }
+    }
+}
+
+impl TestStepFactory for ExpandStepFactory {
+    fn initialize(&self, config: &Config, crate_path: &Path) -> Result<Box<TestStep>> {
+        let annotations = Self::collect_crate_annotations(crate_path)?;
+        let has_expected_file = crate_path.join(EXPECTED_EXPANSION_FILE).exists();
+
+        let expected_expansion = match (has_expected_file, config.bless) {
+            (false, _) if annotations.len() > 0 => None,
+            (false, true) => Some(String::new()),
+            _ => Some(Self::read_expected_expansion(crate_path)?),
+        };
+
+        Ok(Box::new(ExpandStep::new(
+            crate_path.into(),
+            expected_expansion,
+            annotations,
+        )))
+    }
+}
+
+impl TestStep for ExpandStep {
+    fn execute(&self, config: &Config, build_path: &Path) -> Result<()> {
+        let raw_expansion = self.find_actual_expansion(config, build_path)?;
+        let actual_expansion = normalize(&raw_expansion, &config.expansion_normalization)?;
+
+        if let Some(ref expected_expansion) = self.expected_expansion {
+            self.compare_snapshot(config, expected_expansion, &actual_expansion)?;
+        }
+
+        self.check_annotations(&actual_expansion)?;
+
+        if config.expansion_build_check {
+            self.check_expansion_build(config, &raw_expansion, build_path)?;
+        }
+
+        Ok(())
+    }
+
+    fn is_blessed(&self) -> bool {
+        self.blessed.get()
+    }
+}
+
+impl ExpandStep {
+    fn compare_snapshot(
+        &self,
+        config: &Config,
+        expected_expansion: &str,
+        actual_expansion: &str,
+    ) -> Result<()> {
+        let expected_expansion = normalize(expected_expansion, &config.expansion_normalization)?;
+
+        let comparison = match config.expansion_comparison {
+            ExpansionComparison::Tokens => compare_tokens(&expected_expansion, actual_expansion),
+
+            ExpansionComparison::Text => {
+                match actual_expansion.trim_right() == expected_expansion.trim_right() {
+                    true => Ok(()),
+                    false => Err(TestingError::SnapshotMismatch {
+                        name: EXPECTED_EXPANSION_FILE.into(),
+                        expected: expected_expansion,
+                        actual: actual_expansion.into(),
+                    }
+                    .into()),
+                }
+            }
+        };
+
+        match (comparison, config.bless) {
+            (Err(_), true) => {
+                ExpandStepFactory::write_expected_expansion(
+                    &self.crate_dir,
+                    &format!("{}\n", actual_expansion.trim_right()),
+                )?;
+
+                self.blessed.set(true);
+                Ok(())
+            }
+
+            (result, _) => result,
+        }
+    }
+
+    fn check_annotations(&self, actual_expansion: &str) -> Result<()> {
+        if self.annotations.len() == 0 {
+            return Ok(());
+        }
+
+        let expanded_file =
+            syn::parse_file(actual_expansion).context("Unable to parse actual expansion")?;
+
+        let mut unmatched = vec![];
+
+        for annotation in &self.annotations {
+            let source_path = self.crate_dir.join(&annotation.location.file);
+            let source = read_source(&source_path)?;
+
+            let source_file = syn::parse_file(&source)
+                .context(format!("Unable to parse source at {:?}", source_path))?;
+
+            let expanded_items = find_module_items(
+                &expanded_file.items,
+                &module_path(&annotation.location.file),
+            )
+            .and_then(|items| {
+                find_item_expansion(&source_file.items, items, annotation.location.line)
+            })
+            .unwrap_or(&[]);
+
+            let is_matched = match annotation.pattern {
+                MessageType::Regex(ref regex) => {
+                    regex.is_match(&items_source_text(actual_expansion, expanded_items))
+                }
+
+                MessageType::Text(ref snippet) => {
+                    let snippet = canonical_snippet(snippet).unwrap_or_default();
+                    let tokens = expanded_items
+                        .iter()
+                        .map(canonical_item_tokens)
+                        .collect::<Vec<_>>()
+                        .join(" ");
+
+                    tokens.contains(&snippet)
+                }
+
+                MessageType::None => true,
+            };
+
+            if !is_matched {
+                unmatched.push(UnmatchedExpansion {
+                    annotation: annotation.clone(),
+                    expansion: match expanded_items.len() {
+                        0 => None,
+                        _ => Some(items_source_text(actual_expansion, expanded_items)),
+                    },
+                });
+            }
+        }
+
+        if unmatched.len() > 0 {
+            bail!(TestingError::ExpansionAnnotationsFailed { unmatched });
+        }
+
+        Ok(())
+    }
+}
+
+impl ExpandStep {
+    fn check_expansion_build(
+        &self,
+        config: &Config,
+        raw_expansion: &str,
+        build_path: &Path,
+    ) -> Result<()> {
+        let metadata = Metadata::read_resolved(config, &self.crate_dir)?;
+
+        let main_target = metadata
+            .root_package()?
+            .main_target()
+            .ok_or_else(|| format_err!("Unable to find the crate target"))?;
+
+        let proc_macros = metadata.proc_macro_dependencies();
+
+        let crate_dir = self.crate_dir.canonicalize()?;
+        let scratch_dir = tempdir()?;
+        let root_source = main_target.src_path.strip_prefix(&crate_dir)?;
+
+        copy_crate_files(&crate_dir, scratch_dir.as_ref(), Some(root_source))?;
+
+        let mut manifest = String::new();
+
+        File::open(crate_dir.join("Cargo.toml"))?.read_to_string(&mut manifest)?;
+        File::create(scratch_dir.as_ref().join("Cargo.toml"))?.write_all(
+            rewrite_manifest(
+                &crate_dir,
+                &manifest,
+                &proc_macros
+                    .iter()
+                    .map(|item| item.manifest_name.clone())
+                    .collect::<Vec<_>>(),
+            )?
+            .as_bytes(),
+        )?;
+
+        File::create(scratch_dir.as_ref().join(root_source))?.write_all(
+            remove_crate_references(
+                raw_expansion,
+                &proc_macros
+                    .iter()
+                    .map(|item| item.crate_name.clone())
+                    .collect::<Vec<_>>(),
+            )?
+            .as_bytes(),
+        )?;
+
+        let raw_output =
+            cargo::build_command(config, scratch_dir.as_ref(), build_path, "build").output()?;
+        let stderr = String::from_utf8_lossy(&raw_output.stderr).into_owned();
+
+        match raw_output.status.success() {
+            false => bail!(TestingError::ExpandedCrateBuildFailed { stderr }),
+            true => Ok(()),
+        }
+    }
+}
+
+fn module_path(source_path: &Path) -> Vec<String> {
+    let mut path: Vec<String> = source_path
+        .with_extension("")
+        .iter()
+        .skip(1)
+        .map(|item| item.to_string_lossy().into_owned())
+        .collect();
+
+    if path.last().map(|item| item == "mod").unwrap_or(false) {
+        path.pop();
+    }
+
+    match path.as_slice() {
+        [single] if single == "lib" || single == "main" => vec![],
+        _ => path,
+    }
+}
+
+impl fmt::Display for UnmatchedExpansion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "file:      {}:{}",
+            self.annotation.location.file.to_string_lossy(),
+            self.annotation.location.line
+        )?;
+
+        match self.annotation.pattern {
+            MessageType::Regex(ref expr) => writeln!(f, "expected:  Regex({})", expr.as_str())?,
+            MessageType::Text(ref snippet) => writeln!(
+                f,
+                "expected:  {}",
+                formatting::prefix_each_next_line(snippet, "           ")
+            )?,
+
+            MessageType::None => {}
+        }
+
+        match self.expansion {
+            Some(ref expansion) => write!(f, "expansion: {}", expansion),
+            None => write!(f, "expansion: none"),
+        }
+    }
+}
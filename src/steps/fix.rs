@@ -0,0 +1,291 @@
+use failure::ResultExt;
+use serde_json as json;
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+use walkdir::WalkDir;
+
+use super::{read_source, TestStep, TestStepFactory};
+use cargo;
+use cargo_messages::{self, Applicability, DiagnosticMessage};
+use cargo_metadata::Metadata;
+use config::Config;
+use error::{Result, TestingError};
+use scratch::{copy_crate_files, rewrite_manifest};
+
+pub const FIXED_SOURCE_EXTENSION: &str = "fixed";
+
+pub struct FixStepFactory;
+
+struct FixStep {
+    crate_dir: PathBuf,
+    fixed_sources: BTreeMap<PathBuf, String>,
+    build_args: Vec<String>,
+    blessed: Cell<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Suggestion {
+    range: (usize, usize),
+    replacement: String,
+}
+
+impl FixStepFactory {
+    pub fn new() -> Self {
+        FixStepFactory {}
+    }
+
+    pub fn collect_fixed_sources(crate_path: &Path) -> Result<BTreeMap<PathBuf, String>> {
+        let mut sources = BTreeMap::new();
+
+        let entries = WalkDir::new(crate_path)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|entry| entry.depth() > 1 || entry.file_name() != "target");
+
+        for entry in entries {
+            let path = PathBuf::from(entry?.path());
+
+            if path.extension().and_then(|item| item.to_str()) != Some(FIXED_SOURCE_EXTENSION) {
+                continue;
+            }
+
+            sources.insert(
+                path.strip_prefix(crate_path)?.with_extension("rs"),
+                read_source(&path)?,
+            );
+        }
+
+        Ok(sources)
+    }
+
+    pub fn write_fixed_source(crate_path: &Path, source_path: &Path, contents: &str) -> Result<()> {
+        let path = crate_path.join(source_path.with_extension(FIXED_SOURCE_EXTENSION));
+
+        File::create(&path)
+            .context(format!("Unable to write fixed source at {:?}", path))?
+            .write_all(contents.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+impl FixStep {
+    pub fn new(
+        crate_dir: PathBuf,
+        fixed_sources: BTreeMap<PathBuf, String>,
+        build_args: Vec<String>,
+    ) -> Self {
+        FixStep {
+            crate_dir,
+            fixed_sources,
+            build_args,
+            blessed: Cell::new(false),
+        }
+    }
+
+    fn find_suggestions(
+        &self,
+        config: &Config,
+        scratch_dir: &Path,
+        build_path: &Path,
+    ) -> Result<BTreeMap<PathBuf, Vec<Suggestion>>> {
+        let raw_output = cargo::build_command(config, scratch_dir, build_path, "build")
+            .args(&["--message-format", "json"])
+            .args(&self.build_args)
+            .output()?;
+        let stdout = String::from_utf8_lossy(&raw_output.stdout).into_owned();
+
+        let mut suggestions = BTreeMap::new();
+
+        for line in stdout.lines() {
+            let message = {
+                json::from_str::<cargo_messages::Diagnostic>(line)
+                    .context("Unable to parse Cargo JSON output")?
+            };
+
+            match (message.reason.as_str(), message.message) {
+                ("compiler-message", Some(message)) => {
+                    collect_suggestions(&message, &mut suggestions)
+                }
+
+                _ => {}
+            };
+        }
+
+        Ok(suggestions)
+    }
+
+    fn build_fixed_crate(
+        &self,
+        config: &Config,
+        scratch_dir: &Path,
+        build_path: &Path,
+    ) -> Result<()> {
+        let raw_output = cargo::build_command(config, scratch_dir, build_path, "build")
+            .args(&self.build_args)
+            .output()?;
+        let stderr = String::from_utf8_lossy(&raw_output.stderr).into_owned();
+
+        match raw_output.status.success() {
+            false => bail!(TestingError::FixedCrateBuildFailed { stderr }),
+            true => Ok(()),
+        }
+    }
+
+    fn compare_fixed_source(&self, config: &Config, path: &Path, actual: &str) -> Result<()> {
+        let expected = self
+            .fixed_sources
+            .get(path)
+            .map(|item| item.as_str())
+            .unwrap_or("");
+
+        if expected == actual {
+            return Ok(());
+        }
+
+        match config.bless {
+            true => {
+                FixStepFactory::write_fixed_source(&self.crate_dir, path, actual)?;
+
+                self.blessed.set(true);
+                Ok(())
+            }
+
+            false => bail!(TestingError::SnapshotMismatch {
+                name: path
+                    .with_extension(FIXED_SOURCE_EXTENSION)
+                    .to_string_lossy()
+                    .into_owned(),
+
+                expected: expected.into(),
+                actual: actual.into(),
+            }),
+        }
+    }
+}
+
+impl TestStepFactory for FixStepFactory {
+    fn is_applicable(&self, crate_path: &Path) -> bool {
+        Self::collect_fixed_sources(crate_path)
+            .map(|sources| sources.len() > 0)
+            .unwrap_or(true)
+    }
+
+    fn initialize(&self, config: &Config, crate_path: &Path) -> Result<Box<TestStep>> {
+        let fixed_sources = Self::collect_fixed_sources(crate_path)?;
+
+        let build_args = match fixed_sources.len() {
+            0 => vec![],
+            _ => Metadata::read(config, crate_path)?.build_args(),
+        };
+
+        Ok(Box::new(FixStep::new(
+            crate_path.into(),
+            fixed_sources,
+            build_args,
+        )))
+    }
+}
+
+impl TestStep for FixStep {
+    fn execute(&self, config: &Config, build_path: &Path) -> Result<()> {
+        if self.fixed_sources.len() == 0 {
+            return Ok(());
+        }
+
+        let crate_dir = self.crate_dir.canonicalize()?;
+        let scratch_dir = tempdir()?;
+
+        copy_crate_files(&crate_dir, scratch_dir.as_ref(), None)?;
+
+        let mut manifest = String::new();
+
+        File::open(crate_dir.join("Cargo.toml"))?.read_to_string(&mut manifest)?;
+        File::create(scratch_dir.as_ref().join("Cargo.toml"))?
+            .write_all(rewrite_manifest(&crate_dir, &manifest, &[])?.as_bytes())?;
+
+        let suggestions = self.find_suggestions(config, scratch_dir.as_ref(), build_path)?;
+
+        let paths: BTreeSet<_> = suggestions
+            .keys()
+            .chain(self.fixed_sources.keys())
+            .cloned()
+            .collect();
+
+        for path in paths {
+            let source_path = scratch_dir.as_ref().join(&path);
+
+            let fixed_source = apply_suggestions(
+                &read_source(&source_path)?,
+                suggestions
+                    .get(&path)
+                    .map(|item| item.as_slice())
+                    .unwrap_or(&[]),
+            );
+
+            self.compare_fixed_source(config, &path, &fixed_source)?;
+
+            File::create(&source_path)?.write_all(fixed_source.as_bytes())?;
+        }
+
+        self.build_fixed_crate(config, scratch_dir.as_ref(), build_path)
+    }
+
+    fn is_blessed(&self) -> bool {
+        self.blessed.get()
+    }
+}
+
+fn collect_suggestions(
+    message: &DiagnosticMessage,
+    suggestions: &mut BTreeMap<PathBuf, Vec<Suggestion>>,
+) {
+    for span in &message.spans {
+        let path = PathBuf::from(&span.file_name);
+
+        if span.suggestion_applicability != Some(Applicability::MachineApplicable)
+            || path.is_absolute()
+        {
+            continue;
+        }
+
+        if let Some(ref replacement) = span.suggested_replacement {
+            suggestions.entry(path).or_insert(vec![]).push(Suggestion {
+                range: (span.byte_start, span.byte_end),
+                replacement: replacement.clone(),
+            });
+        }
+    }
+
+    for child in &message.children {
+        collect_suggestions(child, suggestions);
+    }
+}
+
+fn apply_suggestions(source: &str, suggestions: &[Suggestion]) -> String {
+    let mut suggestions = suggestions.to_vec();
+
+    suggestions.sort_by_key(|item| item.range);
+    suggestions.dedup();
+
+    let mut output = String::new();
+    let mut position = 0;
+
+    for suggestion in suggestions {
+        let (start, end) = suggestion.range;
+
+        if start < position || end > source.len() {
+            continue;
+        }
+
+        output += &source[position..start];
+        output += &suggestion.replacement;
+        position = end;
+    }
+
+    output + &source[position..]
+}
@@ -1,3 +1,6 @@
+use failure::ResultExt;
+use std::fs::File;
+use std::io::Read;
 use std::path::Path;
 
 use config::Config;
@@ -5,11 +8,35 @@ use error::Result;
 
 pub trait TestStepFactory {
     fn initialize(&self, config: &Config, crate_path: &Path) -> Result<Box<TestStep>>;
+
+    fn is_applicable(&self, _crate_path: &Path) -> bool {
+        true
+    }
 }
 
 pub trait TestStep {
     fn execute(&self, config: &Config, build_path: &Path) -> Result<()>;
+
+    fn is_blessed(&self) -> bool {
+        false
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+fn read_source(path: &Path) -> Result<String> {
+    let mut contents = String::new();
+
+    File::open(path)
+        .context(format!("Unable to open source at {:?}", path))?
+        .read_to_string(&mut contents)?;
+
+    Ok(contents)
 }
 
 pub mod build;
 pub mod check_errors;
+pub mod expand;
+pub mod fix;
@@ -25,6 +25,23 @@ fn it_should_handle_success() {
     );
 }
 
+#[test]
+fn it_should_use_build_args_from_metadata() {
+    let step = BuildStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildSuccess, "example/tests/diagnostics");
+
+    let error = {
+        step.initialize(&config, &Path::new("example/tests/diagnostics/features-1"))
+            .unwrap()
+            .execute(&config, output_path.as_ref())
+            .expect_err("It should build the crate with its features")
+    };
+
+    assert!(error.to_string().contains("error[E0308]: mismatched types"));
+}
+
 #[test]
 fn it_should_handle_fail() {
     let step = BuildStepFactory::new();
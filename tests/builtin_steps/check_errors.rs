@@ -1,12 +1,17 @@
 use regex::Regex;
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use tempfile::tempdir;
 
-use crate_compile_test::config::{Config, Mode};
+use crate_compile_test::config::{Config, MacroBacktrace, Mode};
+use crate_compile_test::error::TestingError;
+use crate_compile_test::matching::NormalizedMatcher;
 use crate_compile_test::steps::TestStepFactory;
 
 use crate_compile_test::steps::check_errors::{
-    CheckErrorsStepFactory, CompilerMessage, DiagnosticLevel, MessageLocation, MessageType,
+    BuildOutput, CheckErrorsStepFactory, CompilerMessage, DiagnosticLevel, FailureStage,
+    MessageLabel, MessageLocation, MessageType,
 };
 
 #[test]
@@ -63,8 +68,9 @@ fn it_should_handle_all_matched_messages() {
 
 #[test]
 fn it_should_collect_expected_messages() {
+    let config = Config::new(Mode::BuildFail, "example/tests/build-fail");
     let crate_path = Path::new("example/tests/build-fail/fail-1");
-    let messages = CheckErrorsStepFactory::collect_crate_messages(&crate_path).unwrap();
+    let messages = CheckErrorsStepFactory::collect_crate_messages(&config, &crate_path).unwrap();
 
     assert_eq!(
         messages,
@@ -76,8 +82,11 @@ fn it_should_collect_expected_messages() {
                 level: DiagnosticLevel::Warning,
                 location: Some(MessageLocation {
                     file: PathBuf::from("src/lib.rs"),
-                    line: 2
-                })
+                    line: 2,
+                    columns: None
+                }),
+                labels: vec![],
+                children: vec![]
             },
             CompilerMessage {
                 message: MessageType::None,
@@ -86,8 +95,11 @@ fn it_should_collect_expected_messages() {
                 level: DiagnosticLevel::Error,
                 location: Some(MessageLocation {
                     file: PathBuf::from("src/lib.rs"),
-                    line: 2
-                })
+                    line: 2,
+                    columns: None
+                }),
+                labels: vec![],
+                children: vec![]
             },
             CompilerMessage {
                 message: MessageType::Text("unresolved import `mod2::func3`".into()),
@@ -96,8 +108,11 @@ fn it_should_collect_expected_messages() {
                 level: DiagnosticLevel::Error,
                 location: Some(MessageLocation {
                     file: PathBuf::from("src/lib.rs"),
-                    line: 2
-                })
+                    line: 2,
+                    columns: None
+                }),
+                labels: vec![],
+                children: vec![]
             },
             CompilerMessage {
                 message: MessageType::None,
@@ -106,8 +121,11 @@ fn it_should_collect_expected_messages() {
                 level: DiagnosticLevel::Error,
                 location: Some(MessageLocation {
                     file: PathBuf::from("src/lib.rs"),
-                    line: 12
-                })
+                    line: 12,
+                    columns: None
+                }),
+                labels: vec![],
+                children: vec![]
             },
             CompilerMessage {
                 message: MessageType::Text("With extra space".into()),
@@ -116,8 +134,11 @@ fn it_should_collect_expected_messages() {
                 level: DiagnosticLevel::Note,
                 location: Some(MessageLocation {
                     file: PathBuf::from("src/lib.rs"),
-                    line: 17
-                })
+                    line: 17,
+                    columns: None
+                }),
+                labels: vec![],
+                children: vec![]
             },
             CompilerMessage {
                 message: MessageType::Text("For previous line".into()),
@@ -126,8 +147,11 @@ fn it_should_collect_expected_messages() {
                 level: DiagnosticLevel::Help,
                 location: Some(MessageLocation {
                     file: PathBuf::from("src/lib.rs"),
-                    line: 17
-                })
+                    line: 17,
+                    columns: None
+                }),
+                labels: vec![],
+                children: vec![]
             },
         ]
     );
@@ -153,10 +177,22 @@ fn it_should_handle_nested_sources() {
     );
 }
 
+#[test]
+fn it_should_reject_virtual_manifests() {
+    let config = Config::new(Mode::BuildFail, "example/tests/rejected");
+    let crate_path = Path::new("example/tests/rejected/virtual-1");
+
+    let error = CheckErrorsStepFactory::collect_crate_messages(&config, &crate_path)
+        .expect_err("It should fail to find the crate package");
+
+    assert!(error.to_string().ends_with("is a virtual manifest"));
+}
+
 #[test]
 fn it_should_collect_messages_from_nested_sources() {
+    let config = Config::new(Mode::BuildFail, "example/tests/build-fail");
     let crate_path = Path::new("example/tests/build-fail/fail-3");
-    let messages = CheckErrorsStepFactory::collect_crate_messages(&crate_path).unwrap();
+    let messages = CheckErrorsStepFactory::collect_crate_messages(&config, &crate_path).unwrap();
 
     assert_eq!(
         messages,
@@ -168,8 +204,11 @@ fn it_should_collect_messages_from_nested_sources() {
                 level: DiagnosticLevel::Error,
                 location: Some(MessageLocation {
                     file: PathBuf::from("src/lib.rs"),
-                    line: 4
-                })
+                    line: 4,
+                    columns: None
+                }),
+                labels: vec![],
+                children: vec![]
             },
             CompilerMessage {
                 message: MessageType::Text("function `func1` is private".into()),
@@ -178,8 +217,11 @@ fn it_should_collect_messages_from_nested_sources() {
                 level: DiagnosticLevel::Error,
                 location: Some(MessageLocation {
                     file: PathBuf::from("src/lib.rs"),
-                    line: 6
-                })
+                    line: 6,
+                    columns: None
+                }),
+                labels: vec![],
+                children: vec![]
             },
             CompilerMessage {
                 message: MessageType::None,
@@ -188,8 +230,11 @@ fn it_should_collect_messages_from_nested_sources() {
                 level: DiagnosticLevel::Error,
                 location: Some(MessageLocation {
                     file: PathBuf::from("src/mod_2/mod.rs"),
-                    line: 1
-                })
+                    line: 1,
+                    columns: None
+                }),
+                labels: vec![],
+                children: vec![]
             },
             CompilerMessage {
                 message: MessageType::Text("With extra space".into()),
@@ -198,8 +243,11 @@ fn it_should_collect_messages_from_nested_sources() {
                 level: DiagnosticLevel::Note,
                 location: Some(MessageLocation {
                     file: PathBuf::from("src/mod_2/mod.rs"),
-                    line: 6
-                })
+                    line: 6,
+                    columns: None
+                }),
+                labels: vec![],
+                children: vec![]
             },
             CompilerMessage {
                 message: MessageType::Text("For previous line".into()),
@@ -208,8 +256,11 @@ fn it_should_collect_messages_from_nested_sources() {
                 level: DiagnosticLevel::Help,
                 location: Some(MessageLocation {
                     file: PathBuf::from("src/mod_2/mod.rs"),
-                    line: 6
-                })
+                    line: 6,
+                    columns: None
+                }),
+                labels: vec![],
+                children: vec![]
             },
             CompilerMessage {
                 message: MessageType::Text("another warning".into()),
@@ -218,8 +269,11 @@ fn it_should_collect_messages_from_nested_sources() {
                 level: DiagnosticLevel::Warning,
                 location: Some(MessageLocation {
                     file: PathBuf::from("src/mod_1.rs"),
-                    line: 1
-                })
+                    line: 1,
+                    columns: None
+                }),
+                labels: vec![],
+                children: vec![]
             },
             CompilerMessage {
                 message: MessageType::None,
@@ -228,8 +282,11 @@ fn it_should_collect_messages_from_nested_sources() {
                 level: DiagnosticLevel::Error,
                 location: Some(MessageLocation {
                     file: PathBuf::from("src/mod_1.rs"),
-                    line: 1
-                })
+                    line: 1,
+                    columns: None
+                }),
+                labels: vec![],
+                children: vec![]
             },
             CompilerMessage {
                 message: MessageType::Text("unresolved import `mod_2::func3`".into()),
@@ -238,8 +295,11 @@ fn it_should_collect_messages_from_nested_sources() {
                 level: DiagnosticLevel::Error,
                 location: Some(MessageLocation {
                     file: PathBuf::from("src/mod_1.rs"),
-                    line: 1
-                })
+                    line: 1,
+                    columns: None
+                }),
+                labels: vec![],
+                children: vec![]
             },
         ]
     );
@@ -306,8 +366,9 @@ fn it_should_use_target_from_config() {
 
 #[test]
 fn it_should_collect_global_messages() {
+    let config = Config::new(Mode::BuildFail, "example/tests/build-fail");
     let crate_path = Path::new("example/tests/build-fail/fail-4");
-    let messages = CheckErrorsStepFactory::collect_crate_messages(&crate_path).unwrap();
+    let messages = CheckErrorsStepFactory::collect_crate_messages(&config, &crate_path).unwrap();
 
     assert_eq!(
         messages,
@@ -318,6 +379,8 @@ fn it_should_collect_global_messages() {
 
                 level: DiagnosticLevel::Note,
                 location: None,
+                labels: vec![],
+                children: vec![],
             },
             CompilerMessage {
                 message: MessageType::Regex(
@@ -327,6 +390,8 @@ fn it_should_collect_global_messages() {
 
                 level: DiagnosticLevel::Note,
                 location: None,
+                labels: vec![],
+                children: vec![],
             },
             CompilerMessage {
                 message: MessageType::Regex(
@@ -336,6 +401,8 @@ fn it_should_collect_global_messages() {
 
                 level: DiagnosticLevel::Note,
                 location: None,
+                labels: vec![],
+                children: vec![],
             },
         ]
     );
@@ -360,3 +427,887 @@ fn it_should_global_errors() {
         read_output!("tests/ui/fail-4.check_errors.output")
     );
 }
+
+#[test]
+fn it_should_collect_column_ranges() {
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+    let crate_path = Path::new("example/tests/diagnostics/columns-1");
+    let messages = CheckErrorsStepFactory::collect_crate_messages(&config, &crate_path).unwrap();
+
+    assert_eq!(
+        messages
+            .into_iter()
+            .map(|item| item.location.unwrap())
+            .map(|location| (location.line, location.columns))
+            .collect::<Vec<_>>(),
+        &[(2, Some((9, 20))), (10, Some((19, 34)))]
+    );
+}
+
+#[test]
+fn it_should_match_column_ranges() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+
+    step.initialize(&config, &Path::new("example/tests/diagnostics/columns-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_collect_labels() {
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+    let crate_path = Path::new("example/tests/diagnostics/labels-1");
+    let messages = CheckErrorsStepFactory::collect_crate_messages(&config, &crate_path).unwrap();
+
+    assert_eq!(
+        messages[0].labels,
+        &[
+            MessageLabel {
+                location: MessageLocation {
+                    file: PathBuf::from("src/lib.rs"),
+                    line: 2,
+                    columns: None,
+                },
+                text: "`Point` redefined here".into(),
+            },
+            MessageLabel {
+                location: MessageLocation {
+                    file: PathBuf::from("src/lib.rs"),
+                    line: 1,
+                    columns: None,
+                },
+                text: "previous definition of the type `Point` here".into(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn it_should_match_labels() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+
+    step.initialize(&config, &Path::new("example/tests/diagnostics/labels-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_reject_labels_without_parent_in_file() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/rejected");
+
+    let error = step
+        .initialize(&config, &Path::new("example/tests/rejected/labels-2"))
+        .and_then(|step| step.execute(&config, output_path.as_ref()))
+        .expect_err("It should reject labels without a parent message");
+
+    let message = error.to_string();
+
+    assert!(message.contains("src/lib.rs:8:1 Annotation can't refer to a GLOBAL message"));
+    assert!(message.contains("src/other.rs:2:10 LABEL annotation without a parent message"));
+}
+
+#[test]
+fn it_should_reject_children_without_parent_in_file() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/rejected");
+
+    let error = step
+        .initialize(&config, &Path::new("example/tests/rejected/children-2"))
+        .and_then(|step| step.execute(&config, output_path.as_ref()))
+        .expect_err("It should reject children without a parent message");
+
+    let message = error.to_string();
+
+    assert!(message.contains("src/lib.rs:8:1 Annotation can't refer to a GLOBAL message"));
+    assert!(message.contains("src/other.rs:1:1 Child annotation without a parent message"));
+}
+
+#[test]
+fn it_should_compare_stderr() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+
+    step.initialize(&config, &Path::new("example/tests/diagnostics/stderr-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_bless_stderr() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+    let crate_path = tempdir().unwrap();
+
+    copy_crate!("example/tests/diagnostics/stderr-1", crate_path.as_ref());
+    File::create(crate_path.as_ref().join("expected.stderr")).unwrap();
+
+    let mut config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+    config.bless = true;
+
+    let step = step.initialize(&config, crate_path.as_ref()).unwrap();
+
+    step.execute(&config, output_path.as_ref())
+        .expect("It should bless the stderr");
+
+    assert!(step.is_blessed());
+    assert_eq!(
+        read_output!(crate_path.as_ref().join("expected.stderr")),
+        read_output!("example/tests/diagnostics/stderr-1/expected.stderr")
+    );
+}
+
+#[test]
+fn it_should_handle_expected_warnings() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildWarnings, "example/tests/build-warnings");
+
+    step.initialize(&config, &Path::new("example/tests/build-warnings/warnings-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_require_success_for_expected_warnings() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildWarnings, "example/tests/build-fail");
+
+    let error = {
+        step.initialize(&config, &Path::new("example/tests/build-fail/fail-2"))
+            .unwrap()
+            .execute(&config, output_path.as_ref())
+            .expect_err("It should fail building the crate")
+    };
+
+    assert!(error.to_string().starts_with("Unable to build the crate!"));
+}
+
+#[test]
+fn it_should_ignore_non_strict_levels() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let mut config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+    config.strict_levels = vec![DiagnosticLevel::Error];
+
+    step.initialize(&config, &Path::new("example/tests/diagnostics/levels-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_check_non_strict_levels_on_annotated_lines() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+    let crate_path = tempdir().unwrap();
+
+    copy_crate!("example/tests/diagnostics/levels-1", crate_path.as_ref());
+
+    let source = read_output!(crate_path.as_ref().join("src/lib.rs"))
+        .replace("    //~^ WARNING variable does not need to be mutable\n", "");
+
+    File::create(crate_path.as_ref().join("src/lib.rs"))
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+
+    let mut config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+    config.strict_levels = vec![DiagnosticLevel::Error];
+
+    let error = {
+        step.initialize(&config, crate_path.as_ref())
+            .unwrap()
+            .execute(&config, output_path.as_ref())
+            .expect_err("It should report the unannotated warning")
+    };
+
+    assert!(
+        error
+            .to_string()
+            .contains("variable does not need to be mutable")
+    );
+
+    assert!(!error.to_string().contains("unused import"));
+}
+
+#[test]
+fn it_should_collect_messages_from_all_targets() {
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+    let crate_path = Path::new("example/tests/diagnostics/targets-1");
+    let messages = CheckErrorsStepFactory::collect_crate_messages(&config, &crate_path).unwrap();
+
+    let mut files: Vec<_> = messages
+        .into_iter()
+        .map(|item| item.location.unwrap().file)
+        .collect();
+
+    files.sort();
+
+    assert_eq!(
+        files,
+        &[PathBuf::from("examples/demo.rs"), PathBuf::from("tests/answer.rs")]
+    );
+}
+
+#[test]
+fn it_should_skip_build_dir_and_outer_sources() {
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+    let root_path = tempdir().unwrap();
+    let crate_path = root_path.as_ref().join("targets-2");
+
+    copy_crate!("example/tests/diagnostics/targets-2", &crate_path);
+
+    create_dir_all(crate_path.join("target/debug")).unwrap();
+    create_dir_all(root_path.as_ref().join("shared")).unwrap();
+
+    File::create(crate_path.join("target/debug/generated.rs"))
+        .unwrap()
+        .write_all(b"//~ ERROR generated")
+        .unwrap();
+
+    File::create(root_path.as_ref().join("shared/main.rs"))
+        .unwrap()
+        .write_all(b"fn main() {} //~ ERROR shared")
+        .unwrap();
+
+    OpenOptions::new()
+        .append(true)
+        .open(crate_path.join("Cargo.toml"))
+        .unwrap()
+        .write_all(b"\n[[bin]]\nname = \"shared\"\npath = \"../shared/main.rs\"\n")
+        .unwrap();
+
+    let messages = CheckErrorsStepFactory::collect_crate_messages(&config, &crate_path).unwrap();
+
+    assert_eq!(
+        messages
+            .into_iter()
+            .map(|item| item.location.unwrap().file)
+            .collect::<Vec<_>>(),
+        &[PathBuf::from("lib.rs")]
+    );
+}
+
+#[test]
+fn it_should_use_build_args_from_metadata() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+
+    step.initialize(&config, &Path::new("example/tests/diagnostics/targets-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_merge_expected_messages_file() {
+    let crate_path = Path::new("example/tests/diagnostics/sidecar-1");
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+    let messages = CheckErrorsStepFactory::collect_crate_messages(&config, &crate_path).unwrap();
+
+    assert_eq!(
+        messages,
+        &[
+            CompilerMessage {
+                message: MessageType::Text("cannot find type `Question` in this scope".into()),
+                level: DiagnosticLevel::Error,
+                code: None,
+                location: Some(MessageLocation {
+                    file: PathBuf::from("src/lib.rs"),
+                    line: 5,
+                    columns: None,
+                }),
+                labels: vec![],
+                children: vec![],
+            },
+            CompilerMessage {
+                message: MessageType::None,
+                level: DiagnosticLevel::Error,
+                code: Some("E0308".into()),
+                location: Some(MessageLocation {
+                    file: PathBuf::from("src/lib.rs"),
+                    line: 2,
+                    columns: None,
+                }),
+                labels: vec![],
+                children: vec![],
+            },
+        ]
+    );
+}
+
+#[test]
+fn it_should_read_json_expected_messages() {
+    let crate_path = Path::new("example/tests/diagnostics/sidecar-2");
+    let messages = CheckErrorsStepFactory::read_expected_messages(&crate_path).unwrap();
+
+    assert_eq!(
+        messages,
+        &[CompilerMessage {
+            message: MessageType::Regex(Regex::new("^mismatched types$").unwrap()),
+            level: DiagnosticLevel::Error,
+            code: None,
+            location: Some(MessageLocation {
+                file: PathBuf::from("src/lib.rs"),
+                line: 2,
+                columns: Some((22, 26)),
+            }),
+            labels: vec![],
+            children: vec![],
+        }]
+    );
+}
+
+#[test]
+fn it_should_check_expected_messages_file() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+
+    for crate_path in &["sidecar-1", "sidecar-2"] {
+        step.initialize(&config, &Path::new("example/tests/diagnostics").join(crate_path))
+            .unwrap()
+            .execute(&config, output_path.as_ref())
+            .expect("It should finish without error");
+    }
+}
+
+#[test]
+fn it_should_reject_invalid_expected_messages() {
+    let crate_path = Path::new("example/tests/rejected/sidecar-3");
+
+    let error = {
+        CheckErrorsStepFactory::read_expected_messages(&crate_path)
+            .expect_err("It should reject a zero count")
+    };
+
+    assert_eq!(
+        error.iter_chain().map(|item| item.to_string()).collect::<Vec<_>>(),
+        &[
+            "Invalid message #1 at \"example/tests/rejected/sidecar-3/expected.toml\"",
+            "`count` should be greater than zero",
+        ]
+    );
+
+    let crate_path = Path::new("example/tests/rejected/sidecar-4");
+
+    let error = {
+        CheckErrorsStepFactory::read_expected_messages(&crate_path)
+            .expect_err("It should reject unknown keys")
+    };
+
+    assert!(error
+        .iter_chain()
+        .any(|item| item.to_string().contains("unknown field `colums`")));
+}
+
+#[test]
+fn it_should_collect_regex_messages() {
+    let crate_path = Path::new("example/tests/diagnostics/regex-1");
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+    let messages = CheckErrorsStepFactory::collect_crate_messages(&config, &crate_path).unwrap();
+
+    assert_eq!(
+        messages,
+        &[
+            CompilerMessage {
+                message: MessageType::Regex(Regex::new("^mismatched types$").unwrap()),
+                level: DiagnosticLevel::Error,
+                code: None,
+                location: Some(MessageLocation {
+                    file: PathBuf::from("src/lib.rs"),
+                    line: 4,
+                    columns: None,
+                }),
+                labels: vec![],
+                children: vec![],
+            },
+            CompilerMessage {
+                message: MessageType::Regex(
+                    Regex::new("a value of type `Vec<.+>` cannot be built from .+").unwrap(),
+                ),
+                level: DiagnosticLevel::Error,
+                code: None,
+                location: Some(MessageLocation {
+                    file: PathBuf::from("src/lib.rs"),
+                    line: 8,
+                    columns: None,
+                }),
+                labels: vec![],
+                children: vec![],
+            },
+        ]
+    );
+}
+
+#[test]
+fn it_should_check_regex_messages() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+
+    step.initialize(&config, &Path::new("example/tests/diagnostics/regex-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_collect_repeated_messages() {
+    let crate_path = Path::new("example/tests/diagnostics/repeat-1");
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+    let messages = CheckErrorsStepFactory::collect_crate_messages(&config, &crate_path).unwrap();
+
+    let message = CompilerMessage {
+        message: MessageType::Text("cannot find type `Point` in this scope".into()),
+        level: DiagnosticLevel::Error,
+        code: None,
+        location: Some(MessageLocation {
+            file: PathBuf::from("src/lib.rs"),
+            line: 1,
+            columns: None,
+        }),
+        labels: vec![],
+        children: vec![],
+    };
+
+    assert_eq!(messages, &[message.clone(), message]);
+}
+
+#[test]
+fn it_should_match_repeated_messages() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+
+    step.initialize(&config, &Path::new("example/tests/diagnostics/repeat-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_report_unmatched_duplicates() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+    let crate_path = tempdir().unwrap();
+
+    copy_crate!("example/tests/diagnostics/repeat-1", crate_path.as_ref());
+
+    let source = read_output!(crate_path.as_ref().join("src/lib.rs"));
+    let source = source.replace("ERROR[2]", "ERROR");
+
+    File::create(crate_path.as_ref().join("src/lib.rs"))
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+
+    let error = step
+        .initialize(&config, crate_path.as_ref())
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect_err("It should fail");
+
+    assert!(
+        error
+            .to_string()
+            .contains("Unexpected messages:\n └─ file:    src/lib.rs:1:34-39\n")
+    );
+}
+
+#[test]
+fn it_should_use_message_matcher_from_config() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+    let crate_path = tempdir().unwrap();
+
+    copy_crate!("example/tests/diagnostics/repeat-1", crate_path.as_ref());
+
+    let source = read_output!(crate_path.as_ref().join("src/lib.rs"));
+    let source = source.replace("`Point` in this", "Point in  this");
+
+    File::create(crate_path.as_ref().join("src/lib.rs"))
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+
+    let mut config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+
+    step.initialize(&config, crate_path.as_ref())
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect_err("It should fail");
+
+    config.message_matcher = Box::new(NormalizedMatcher);
+
+    step.initialize(&config, crate_path.as_ref())
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_collect_child_messages() {
+    let crate_path = Path::new("example/tests/diagnostics/children-1");
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+    let messages = CheckErrorsStepFactory::collect_crate_messages(&config, &crate_path).unwrap();
+
+    assert_eq!(
+        messages
+            .into_iter()
+            .map(|item| item.children)
+            .collect::<Vec<_>>(),
+        &[
+            vec![CompilerMessage {
+                message: MessageType::Text("consider making this binding mutable".into()),
+                level: DiagnosticLevel::Help,
+                code: None,
+                location: None,
+                labels: vec![],
+                children: vec![],
+            }],
+            vec![CompilerMessage {
+                message: MessageType::Text("consider borrowing here".into()),
+                level: DiagnosticLevel::Help,
+                code: None,
+                location: None,
+                labels: vec![],
+                children: vec![],
+            }],
+        ]
+    );
+}
+
+#[test]
+fn it_should_match_child_messages() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+
+    step.initialize(&config, &Path::new("example/tests/diagnostics/children-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_attribute_messages_to_macro_call_sites() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let mut config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+
+    let error = step
+        .initialize(&config, &Path::new("example/tests/diagnostics/macros-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect_err("It should fail");
+
+    assert!(
+        error
+            .to_string()
+            .contains("Unexpected messages:\n ├─ file:    src/lib.rs:6:13-17\n")
+    );
+
+    config.macro_backtrace = MacroBacktrace::CallSite;
+
+    step.initialize(&config, &Path::new("example/tests/diagnostics/macros-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_report_malformed_annotations() {
+    let step = CheckErrorsStepFactory::new();
+    let config = Config::new(Mode::BuildFail, "example/tests/rejected");
+
+    let error = step
+        .initialize(&config, &Path::new("example/tests/rejected/malformed-1"))
+        .err()
+        .expect("It should fail before building the crate");
+
+    assert_eq!(
+        error.to_string(),
+        read_output!("tests/ui/malformed_annotations.output")
+    );
+}
+
+#[test]
+fn it_should_ignore_annotation_lookalikes() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+    let step = step
+        .initialize(&config, &Path::new("example/tests/diagnostics/comments-1"))
+        .unwrap();
+
+    step.execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+
+    assert!(step.warnings().is_empty());
+}
+
+#[test]
+fn it_should_collect_lint_codes() {
+    let crate_path = Path::new("example/tests/build-warnings/lints-1");
+    let config = Config::new(Mode::BuildWarnings, "example/tests/build-warnings");
+    let messages = CheckErrorsStepFactory::collect_crate_messages(&config, &crate_path).unwrap();
+
+    assert_eq!(
+        messages
+            .into_iter()
+            .map(|item| match item.message {
+                MessageType::Text(text) => (item.level, item.code, Some(text)),
+                _ => (item.level, item.code, None),
+            })
+            .collect::<Vec<_>>(),
+        &[
+            (DiagnosticLevel::Warning, Some("dead_code".into()), None),
+            (DiagnosticLevel::Warning, None, Some("unused_variables".into())),
+            (DiagnosticLevel::Warning, None, Some("deprecated".into())),
+        ]
+    );
+}
+
+#[test]
+fn it_should_match_lint_codes() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildWarnings, "example/tests/build-warnings");
+
+    step.initialize(&config, &Path::new("example/tests/build-warnings/lints-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_accept_linker_errors_for_link_stage() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let mut config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+    config.strict_levels = vec![DiagnosticLevel::Error];
+
+    step.initialize(&config, &Path::new("example/tests/diagnostics/link-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_classify_failure_stage_by_primary_cause() {
+    assert_eq!(
+        read_build_output("link-only").failure_stage(),
+        FailureStage::Link
+    );
+
+    assert_eq!(
+        read_build_output("panic-only").failure_stage(),
+        FailureStage::ProcMacroPanic
+    );
+
+    assert_eq!(
+        read_build_output("panic-mixed").failure_stage(),
+        FailureStage::Compile,
+        "It should not blame the panic when ordinary errors are present"
+    );
+}
+
+#[test]
+fn it_should_read_failure_stage() {
+    assert_eq!(
+        CheckErrorsStepFactory::read_failure_stage(Path::new("example/tests/build-fail/fail-4"))
+            .unwrap(),
+        FailureStage::Link
+    );
+
+    assert_eq!(
+        CheckErrorsStepFactory::read_failure_stage(Path::new("example/tests/build-fail/fail-1"))
+            .unwrap(),
+        FailureStage::Compile
+    );
+}
+
+#[test]
+fn it_should_handle_expected_failure_stage() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+
+    step.initialize(&config, &Path::new("example/tests/diagnostics/stage-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_report_unexpected_failure_stage() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+    let crate_path = tempdir().unwrap();
+
+    copy_crate!("example/tests/diagnostics/regex-1", crate_path.as_ref());
+
+    File::create(crate_path.as_ref().join("expected.toml"))
+        .unwrap()
+        .write_all(b"failure-stage = \"link\"\n")
+        .unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+
+    let error = step
+        .initialize(&config, crate_path.as_ref())
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect_err("It should fail");
+
+    assert_eq!(
+        error.to_string(),
+        "Crate build failed at an unexpected stage!\n\nexpected: link\nactual:   compile"
+    );
+}
+
+#[test]
+fn it_should_handle_manifest_failure_stage() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+
+    step.initialize(&config, &Path::new("example/tests/diagnostics/manifest-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should accept unresolved dependencies");
+
+    let crate_path = tempdir().unwrap();
+
+    copy_crate!("example/tests/diagnostics/manifest-1", crate_path.as_ref());
+
+    File::create(crate_path.as_ref().join("Cargo.toml"))
+        .unwrap()
+        .write_all(b"[package\nname = \"manifest-1\"\n")
+        .unwrap();
+
+    step.initialize(&config, crate_path.as_ref())
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should accept unparsable manifests");
+}
+
+#[test]
+fn it_should_recognise_manifest_errors() {
+    let manifest_output = BuildOutput::new(
+        false,
+        String::new(),
+        "error: failed to parse manifest at `/path/to/Cargo.toml`\n".into(),
+    );
+
+    assert_eq!(
+        manifest_output.unwrap().failure_stage(),
+        FailureStage::Manifest
+    );
+
+    let unknown_output = BuildOutput::new(
+        false,
+        String::new(),
+        "error: could not execute process `rustc -vV` (never executed)\n".into(),
+    );
+
+    assert_eq!(
+        unknown_output.unwrap().failure_stage(),
+        FailureStage::Compile,
+        "It should not blame the manifest for unknown failures"
+    );
+}
+
+#[test]
+fn it_should_accept_annotated_proc_macro_panics() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let mut config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+    config.deny_proc_macro_panics = true;
+
+    step.initialize(&config, &Path::new("example/tests/diagnostics/panic-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_report_proc_macro_panics() {
+    let step = CheckErrorsStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let mut config = Config::new(Mode::BuildFail, "example/tests/rejected");
+    config.deny_proc_macro_panics = true;
+
+    let error = step
+        .initialize(&config, &Path::new("example/tests/rejected/panic-2"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect_err("It should fail");
+
+    assert_eq!(
+        error.to_string(),
+        read_output!("tests/ui/proc_macro_panic.output")
+    );
+}
+
+#[test]
+fn it_should_report_compiler_crashes() {
+    let output = read_build_output("ice-1");
+
+    let error = TestingError::CompilerCrashed {
+        message: output.ice_message().expect("It should detect the crash"),
+        query_stack: output.query_stack(),
+    };
+
+    assert_eq!(
+        error.to_string(),
+        read_output!("tests/ui/compiler_crash.output")
+    );
+}
+
+fn read_build_output(name: &str) -> BuildOutput {
+    BuildOutput::new(
+        false,
+        read_output!(format!("example/tests/outputs/{}.json", name)),
+        read_output!(format!("example/tests/outputs/{}.stderr", name)),
+    )
+    .unwrap()
+}
@@ -0,0 +1,353 @@
+use std::fs::{remove_file, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+use crate_compile_test::config::{Config, ExpansionComparison, Mode};
+use crate_compile_test::steps::check_errors::MessageType;
+use crate_compile_test::steps::{expand::ExpandStepFactory, TestStepFactory};
+
+fn expand_config() -> Config {
+    let mut config = Config::new(Mode::Expand, "example/tests/expand");
+    config.add_cargo_env("RUSTC_BOOTSTRAP", "1");
+
+    config
+}
+
+#[test]
+fn it_should_handle_matching_expansion() {
+    let step = ExpandStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = expand_config();
+
+    step.initialize(&config, &Path::new("example/tests/expand/expand-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should match the expected expansion");
+}
+
+#[test]
+fn it_should_handle_expansion_mismatch() {
+    let step = ExpandStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = expand_config();
+
+    let error = {
+        step.initialize(&config, &Path::new("example/tests/expand/expand-2"))
+            .unwrap()
+            .execute(&config, output_path.as_ref())
+            .expect_err("It should fail comparing the expansion")
+    };
+
+    assert_eq!(
+        error.to_string(),
+        read_output!("tests/ui/expand-2.expand.output")
+    );
+}
+
+#[test]
+fn it_should_require_expected_expansion() {
+    let step = ExpandStepFactory::new();
+    let config = expand_config();
+
+    let error = {
+        step.initialize(&config, &Path::new("example/tests/build-success/success-1"))
+            .err()
+            .expect("It should fail to initialize")
+    };
+
+    assert!(
+        error
+            .to_string()
+            .starts_with("Unable to open expected expansion")
+    );
+}
+
+#[test]
+fn it_should_use_cargo_env_from_config() {
+    let step = ExpandStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let mut config = expand_config();
+    config.add_cargo_env("RUSTFLAGS", "--non-existing-flag");
+
+    let error = {
+        step.initialize(&config, &Path::new("example/tests/expand/expand-1"))
+            .unwrap()
+            .execute(&config, output_path.as_ref())
+            .expect_err("It should fail")
+    };
+
+    assert!(
+        error
+            .to_string()
+            .contains("Unrecognized option: \'non-existing-flag\'")
+    );
+}
+
+#[test]
+fn it_should_bless_missing_expansion() {
+    let step = ExpandStepFactory::new();
+    let output_path = tempdir().unwrap();
+    let crate_path = tempdir().unwrap();
+
+    copy_crate!("example/tests/expand/expand-2", crate_path.as_ref());
+    remove_file(crate_path.as_ref().join("expanded.rs")).unwrap();
+
+    let mut config = expand_config();
+    config.bless = true;
+
+    let step = step.initialize(&config, crate_path.as_ref()).unwrap();
+
+    step.execute(&config, output_path.as_ref())
+        .expect("It should bless the expansion");
+
+    assert!(step.is_blessed());
+    assert_eq!(
+        read_output!(crate_path.as_ref().join("expanded.rs")),
+        read_output!("example/tests/expand/expand-1/expanded.rs")
+    );
+}
+
+#[test]
+fn it_should_compare_expansion_tokens() {
+    let step = ExpandStepFactory::new();
+    let output_path = tempdir().unwrap();
+    let crate_path = tempdir().unwrap();
+
+    copy_crate!("example/tests/expand/expand-1", crate_path.as_ref());
+
+    File::create(crate_path.as_ref().join("expanded.rs"))
+        .unwrap()
+        .write_all(
+            br#"
+                macro_rules! getter {
+                    ($name:ident: $ty:ty = $value:expr,) => {
+                        pub fn $name() -> $ty { $value }
+                    };
+                }
+
+                // The answer to everything.
+                pub fn answer() -> u32 {
+                    42
+                }
+            "#,
+        )
+        .unwrap();
+
+    let mut config = expand_config();
+
+    config.expansion_comparison = ExpansionComparison::Tokens;
+
+    step.initialize(&config, crate_path.as_ref())
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should ignore formatting, comments and trailing commas");
+
+    config.expansion_comparison = ExpansionComparison::Text;
+
+    step.initialize(&config, crate_path.as_ref())
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect_err("It should fail comparing the expansion text");
+}
+
+#[test]
+fn it_should_handle_expansion_tokens_mismatch() {
+    let step = ExpandStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let mut config = expand_config();
+    config.expansion_comparison = ExpansionComparison::Tokens;
+
+    let error = {
+        step.initialize(&config, &Path::new("example/tests/expand/expand-2"))
+            .unwrap()
+            .execute(&config, output_path.as_ref())
+            .expect_err("It should fail comparing the expansion")
+    };
+
+    assert_eq!(
+        error.to_string(),
+        read_output!("tests/ui/expand-2.expand_tokens.output")
+    );
+}
+
+#[test]
+fn it_should_collect_expansion_annotations() {
+    let crate_path = Path::new("example/tests/expand/expand-3");
+    let annotations = ExpandStepFactory::collect_crate_annotations(&crate_path).unwrap();
+
+    let annotations: Vec<_> = annotations
+        .into_iter()
+        .map(|item| {
+            let pattern = match item.pattern {
+                MessageType::Regex(regex) => format!("Regex({})", regex.as_str()),
+                MessageType::Text(text) => format!("Text({})", text),
+                MessageType::None => "None".into(),
+            };
+
+            (item.location.file, item.location.line, pattern)
+        })
+        .collect();
+
+    assert_eq!(
+        annotations,
+        &[
+            (
+                PathBuf::from("src/lib.rs"),
+                15,
+                String::from(r"Regex(pub fn answer\(\) -> u32 \{ 42 \})"),
+            ),
+            (
+                PathBuf::from("src/lib.rs"),
+                17,
+                String::from("Regex(impl ::core::clone::Clone for Point)"),
+            ),
+            (
+                PathBuf::from("src/lib.rs"),
+                23,
+                String::from("Text(pub const LIMIT: u32 = 10;)"),
+            ),
+        ]
+    );
+}
+
+#[test]
+fn it_should_handle_matched_annotations() {
+    let step = ExpandStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = expand_config();
+
+    step.initialize(&config, &Path::new("example/tests/expand/expand-3"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should fulfill all annotations");
+}
+
+#[test]
+fn it_should_handle_unmatched_annotations() {
+    let step = ExpandStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = expand_config();
+
+    let error = {
+        step.initialize(&config, &Path::new("example/tests/expand/expand-4"))
+            .unwrap()
+            .execute(&config, output_path.as_ref())
+            .expect_err("It should fail checking the annotations")
+    };
+
+    assert_eq!(
+        error.to_string(),
+        read_output!("tests/ui/expand-4.expand.output")
+    );
+}
+
+#[test]
+fn it_should_report_malformed_annotations() {
+    let crate_path = Path::new("example/tests/rejected/expands-to-1");
+
+    let error = ExpandStepFactory::collect_crate_annotations(&crate_path)
+        .expect_err("It should fail on malformed annotations");
+
+    assert_eq!(
+        error.to_string(),
+        read_output!("tests/ui/malformed_expansion_annotations.output")
+    );
+}
+
+#[test]
+fn it_should_build_expansion_without_proc_macros() {
+    let step = ExpandStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let mut config = expand_config();
+    config.expansion_build_check = true;
+
+    step.initialize(&config, &Path::new("example/tests/expand/expand-5"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should build the expanded crate");
+}
+
+#[test]
+fn it_should_report_expanded_build_failure() {
+    let step = ExpandStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let mut config = expand_config();
+
+    step.initialize(&config, &Path::new("example/tests/expand/expand-6"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should pass without the build check");
+
+    config.expansion_build_check = true;
+
+    let error = {
+        step.initialize(&config, &Path::new("example/tests/expand/expand-6"))
+            .unwrap()
+            .execute(&config, output_path.as_ref())
+            .expect_err("It should fail building the expanded crate")
+    };
+
+    assert!(
+        error
+            .to_string()
+            .starts_with("Unable to build the expanded crate!")
+    );
+
+    assert!(error.to_string().contains("error[E0308]: mismatched types"));
+}
+
+#[test]
+fn it_should_keep_trailing_comma_of_single_tuples() {
+    let step = ExpandStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let mut config = expand_config();
+    config.expansion_comparison = ExpansionComparison::Tokens;
+
+    let error = {
+        step.initialize(&config, &Path::new("example/tests/expand/expand-7"))
+            .unwrap()
+            .execute(&config, output_path.as_ref())
+            .expect_err("It should tell `(u8,)` and `(u8)` apart")
+    };
+
+    assert_eq!(
+        error.to_string(),
+        read_output!("tests/ui/expand-7.expand_tokens.output")
+    );
+}
+
+#[test]
+fn it_should_match_snippets_with_nested_generics() {
+    let step = ExpandStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = expand_config();
+
+    step.initialize(&config, &Path::new("example/tests/expand/expand-8"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should match `>>` in the snippet");
+}
+
+#[test]
+fn it_should_expand_library_of_mixed_crates() {
+    let step = ExpandStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = expand_config();
+
+    step.initialize(&config, &Path::new("example/tests/expand/expand-9"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should expand the library target");
+}
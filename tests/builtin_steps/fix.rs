@@ -0,0 +1,94 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use tempfile::tempdir;
+
+use crate_compile_test::config::{Config, Mode};
+use crate_compile_test::steps::{fix::FixStepFactory, TestStepFactory};
+
+#[test]
+fn it_should_check_fixed_sources() {
+    let step = FixStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+
+    step.initialize(&config, &Path::new("example/tests/diagnostics/fix-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_skip_crates_without_fixed_sources() {
+    let step = FixStepFactory::new();
+    let output_path = tempdir().unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/build-fail");
+
+    step.initialize(&config, &Path::new("example/tests/build-fail/fail-1"))
+        .unwrap()
+        .execute(&config, output_path.as_ref())
+        .expect("It should finish without error");
+}
+
+#[test]
+fn it_should_apply_only_to_crates_with_fixed_sources() {
+    let step = FixStepFactory::new();
+
+    assert!(step.is_applicable(&Path::new("example/tests/diagnostics/fix-1")));
+    assert!(!step.is_applicable(&Path::new("example/tests/build-fail/fail-1")));
+}
+
+#[test]
+fn it_should_handle_fixed_source_mismatch() {
+    let step = FixStepFactory::new();
+    let output_path = tempdir().unwrap();
+    let crate_path = tempdir().unwrap();
+
+    copy_crate!("example/tests/diagnostics/fix-1", crate_path.as_ref());
+
+    File::create(crate_path.as_ref().join("src/lib.fixed"))
+        .unwrap()
+        .write_all(b"pub fn answer() -> u32 {\n    42\n}\n")
+        .unwrap();
+
+    let config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+
+    let error = {
+        step.initialize(&config, crate_path.as_ref())
+            .unwrap()
+            .execute(&config, output_path.as_ref())
+            .expect_err("It should fail comparing the fixed source")
+    };
+
+    assert!(
+        error
+            .to_string()
+            .starts_with("Snapshot `src/lib.fixed` doesn't match expectations!")
+    );
+}
+
+#[test]
+fn it_should_bless_fixed_sources() {
+    let step = FixStepFactory::new();
+    let output_path = tempdir().unwrap();
+    let crate_path = tempdir().unwrap();
+
+    copy_crate!("example/tests/diagnostics/fix-1", crate_path.as_ref());
+    File::create(crate_path.as_ref().join("src/lib.fixed")).unwrap();
+
+    let mut config = Config::new(Mode::BuildFail, "example/tests/diagnostics");
+    config.bless = true;
+
+    let step = step.initialize(&config, crate_path.as_ref()).unwrap();
+
+    step.execute(&config, output_path.as_ref())
+        .expect("It should bless the fixed source");
+
+    assert!(step.is_blessed());
+    assert_eq!(
+        read_output!(crate_path.as_ref().join("src/lib.fixed")),
+        read_output!("example/tests/diagnostics/fix-1/src/lib.fixed")
+    );
+}
@@ -1,2 +1,4 @@
 mod build;
 mod check_errors;
+mod expand;
+mod fix;
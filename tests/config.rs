@@ -0,0 +1,15 @@
+extern crate crate_compile_test;
+
+use crate_compile_test::config::is_bless_enabled;
+
+#[test]
+fn it_should_enable_bless_only_for_truthy_values() {
+    assert!(is_bless_enabled(Some("1")));
+    assert!(is_bless_enabled(Some("true")));
+    assert!(is_bless_enabled(Some("TRUE")));
+
+    assert!(!is_bless_enabled(Some("0")));
+    assert!(!is_bless_enabled(Some("")));
+    assert!(!is_bless_enabled(Some("false")));
+    assert!(!is_bless_enabled(None::<&str>));
+}
@@ -0,0 +1,58 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+#[macro_use]
+mod utils;
+
+extern crate colored;
+extern crate crate_compile_test;
+
+use crate_compile_test::prelude::*;
+
+#[test]
+fn it_should_display_snapshot_diff() {
+    colored::control::set_override(false);
+
+    let expected = (1..21).map(|line| format!("line {}\n", line)).collect::<String>();
+    let actual = expected
+        .replace("line 3\n", "line three\n")
+        .replace("line 15\n", "")
+        + "line 21\n";
+
+    let error = TestingError::SnapshotMismatch {
+        name: "expected.txt".into(),
+        expected,
+        actual,
+    };
+
+    assert_eq!(
+        error.to_string(),
+        read_output!("tests/ui/snapshot_mismatch.output")
+    );
+}
+
+#[test]
+fn it_should_display_diff_of_large_snapshots() {
+    colored::control::set_override(false);
+
+    let expected = (1..100_001)
+        .map(|line| format!("line {}\n", line))
+        .collect::<String>();
+
+    let actual = expected.replace("line 50000\n", "line fifty thousand\n");
+
+    let error = TestingError::SnapshotMismatch {
+        name: "expected.txt".into(),
+        expected,
+        actual,
+    };
+
+    assert!(error.to_string().contains(concat!(
+        "  @@ -49997,7 +49997,7 @@\n",
+        "   line 49997\n",
+        "   line 49998\n",
+        "   line 49999\n",
+        "  -line 50000\n",
+        "  +line fifty thousand\n",
+    )));
+}
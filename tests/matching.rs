@@ -0,0 +1,44 @@
+extern crate crate_compile_test;
+
+use crate_compile_test::matching::{
+    CaseInsensitiveMatcher, ExactMatcher, MessageMatcher, NormalizedMatcher, SubstringMatcher,
+};
+
+#[test]
+fn it_should_match_exactly() {
+    assert!(ExactMatcher.is_match("mismatched types", "mismatched types"));
+    assert!(!ExactMatcher.is_match("mismatched type", "mismatched types"));
+}
+
+#[test]
+fn it_should_match_substrings() {
+    assert!(SubstringMatcher.is_match("cannot find type", "cannot find type `A` in this scope"));
+    assert!(!SubstringMatcher.is_match("cannot find value", "cannot find type `A` in this scope"));
+}
+
+#[test]
+fn it_should_match_normalized() {
+    assert!(NormalizedMatcher.is_match(
+        "cannot find type Point in  this scope",
+        "cannot find type `Point` in this scope"
+    ));
+
+    assert!(!NormalizedMatcher.is_match(
+        "cannot find type Point",
+        "cannot find type `Point` in this scope"
+    ));
+}
+
+#[test]
+fn it_should_match_case_insensitive() {
+    assert!(CaseInsensitiveMatcher.is_match("Mismatched Types", "mismatched types"));
+    assert!(!CaseInsensitiveMatcher.is_match("Mismatched Type", "mismatched types"));
+}
+
+#[test]
+fn it_should_match_with_custom_functions() {
+    let matcher = |expected: &str, actual: &str| actual.starts_with(expected);
+
+    assert!(matcher.is_match("unused", "unused variable: `value`"));
+    assert!(!matcher.is_match("variable", "unused variable: `value`"));
+}
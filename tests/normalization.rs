@@ -0,0 +1,86 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+extern crate crate_compile_test;
+extern crate regex;
+
+use crate_compile_test::normalization::{normalize, Normalization};
+use regex::Regex;
+
+#[test]
+fn it_should_strip_prelude() {
+    let input = r#"#![feature(prelude_import)]
+#![no_std]
+extern crate std;
+#[prelude_import]
+use ::std::prelude::rust_2015::*;
+#[macro_use]
+extern crate std;
+#[prelude_import]
+use std::prelude::v1::*;
+pub fn answer() -> u32 { 42 }
+"#;
+
+    assert_eq!(
+        normalize(input, &[Normalization::StripPrelude]).unwrap(),
+        "pub fn answer() -> u32 { 42 }\n"
+    );
+}
+
+#[test]
+fn it_should_keep_user_written_extern_crate_std() {
+    let input = r#"#![feature(prelude_import)]
+#![no_std]
+#[prelude_import]
+use std::prelude::v1::*;
+#[macro_use]
+extern crate std;
+extern crate std;
+pub fn answer() -> u32 { 42 }
+"#;
+
+    assert_eq!(
+        normalize(input, &[Normalization::StripPrelude]).unwrap(),
+        "extern crate std;\npub fn answer() -> u32 { 42 }\n"
+    );
+}
+
+#[test]
+fn it_should_keep_whitespace_by_default() {
+    let input = "pub fn answer()\n    -> u32 {\n    \"forty  two\".len() as u32\n}\n";
+
+    assert_eq!(normalize(input, &Normalization::default_pipeline()).unwrap(), input);
+}
+
+#[test]
+fn it_should_strip_hygiene() {
+    let input = "fn answer /* 0#0 */() -> u32 { let x /* 5#2 */ = 42; x /* 5#2 */ }";
+
+    assert_eq!(
+        normalize(input, &[Normalization::StripHygiene]).unwrap(),
+        "fn answer() -> u32 { let x = 42; x }"
+    );
+}
+
+#[test]
+fn it_should_normalize_whitespace() {
+    let input = "pub fn answer()\n    ->\n\n        u32   {\t42 }  \n";
+
+    assert_eq!(
+        normalize(input, &[Normalization::NormalizeWhitespace]).unwrap(),
+        "pub fn answer()\n->\nu32 { 42 }"
+    );
+}
+
+#[test]
+fn it_should_apply_replacements_in_order() {
+    let pipeline = vec![
+        Normalization::Replace(Regex::new(r"__impl_\d+").unwrap(), "__impl_N".into()),
+        Normalization::Custom(Box::new(|input| input.to_uppercase())),
+    ];
+
+    assert_eq!(
+        normalize("const __impl_1234: () = ();", &pipeline).unwrap(),
+        "CONST __IMPL_N: () = ();"
+    );
+}
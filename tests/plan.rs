@@ -1,4 +1,5 @@
 use std::path::{Path, PathBuf};
+use tempfile::tempdir;
 
 #[macro_use]
 extern crate pretty_assertions;
@@ -6,6 +7,8 @@ extern crate pretty_assertions;
 #[macro_use]
 extern crate failure;
 extern crate colored;
+extern crate tempfile;
+extern crate walkdir;
 
 #[macro_use]
 mod utils;
@@ -216,3 +219,43 @@ fn it_should_report_multiple_tests() {
         read_output!("tests/ui/complete.multiple.output")
     );
 }
+
+#[test]
+fn it_should_bless_expansions() {
+    colored::control::set_override(false);
+
+    let base_path = tempdir().unwrap();
+    let crate_path = base_path.as_ref().join("expand-2");
+
+    copy_crate!("example/tests/expand/expand-2", &crate_path);
+
+    let mut actual_output_bytes: Vec<u8> = Vec::new();
+
+    let result = {
+        let mut runner = TestRunner::new(&mut actual_output_bytes);
+        let base_path = base_path.as_ref().to_owned();
+
+        runner.add("bless", move || {
+            let mut config = Config::new(Mode::Expand, &base_path);
+
+            config.add_cargo_env("RUSTC_BOOTSTRAP", "1");
+            config.bless = true;
+
+            config
+        });
+
+        runner.start().unwrap()
+    };
+
+    assert_eq!(result.is_success(), true);
+    assert_eq!(
+        String::from_utf8_lossy(&actual_output_bytes),
+        read_output!("tests/ui/complete.bless.output")
+            .replace("$CRATE_PATH", &crate_path.to_string_lossy())
+    );
+
+    assert_eq!(
+        read_output!(crate_path.join("expanded.rs")),
+        read_output!("example/tests/expand/expand-1/expanded.rs")
+    );
+}
@@ -7,5 +7,6 @@ mod utils;
 extern crate crate_compile_test;
 extern crate regex;
 extern crate tempfile;
+extern crate walkdir;
 
 mod builtin_steps;
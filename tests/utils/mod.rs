@@ -10,3 +10,25 @@ macro_rules! read_output {
         contents
     }};
 }
+
+#[allow(unused_macros)]
+macro_rules! copy_crate {
+    ($from:expr, $to:expr) => {{
+        use std::fs::{copy, create_dir_all};
+        use std::path::Path;
+
+        let from = Path::new($from);
+        let to = Path::new($to);
+
+        for entry in ::walkdir::WalkDir::new(from) {
+            let entry = entry.unwrap();
+            let destination = to.join(entry.path().strip_prefix(from).unwrap());
+
+            if entry.path().is_dir() {
+                create_dir_all(destination).unwrap();
+            } else {
+                copy(entry.path(), destination).unwrap();
+            }
+        }
+    }};
+}